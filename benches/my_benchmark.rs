@@ -4,6 +4,7 @@ extern crate criterion;
 use criterion::Criterion;
 use criterion::black_box;
 use std::fs::File;
+use std::io::BufReader;
 
 //internal
 extern crate togatog_ai;
@@ -11,24 +12,26 @@ extern crate togatog_ai;
 use togatog_ai::solver_config::SolverConfig;
 use togatog_ai::solver::Solver;
 use togatog_ai::search_result::SearchResult;
+use togatog_ai::protocol::ProtocolReader;
 
 fn solver_think_from_file(pack_file_name: &str, info_file_name: &str, config: SolverConfig) -> SearchResult {
     let pack_file = File::open(pack_file_name).expect("can't open a file");
     let info_file = File::open(info_file_name).expect("can't open a file");
     //read from pack file
-    let mut pack = togatog_ai::scanner::Scanner { stdin: pack_file };
-    let mut info = togatog_ai::scanner::Scanner { stdin: info_file };
+    let mut pack = ProtocolReader::new(BufReader::new(pack_file));
+    let mut info = ProtocolReader::new(BufReader::new(info_file));
     //solver object
     let mut solver = togatog_ai::solver::Solver::default();
-    solver.set_packs(togatog_ai::solver::Solver::read_packs(&mut pack));
+    solver.set_packs(pack.read_raw_packs().expect("invalid pack file"));
     //read information only one turn
-    let current_turn: usize = info.read();
-    let player = togatog_ai::solver::Solver::read_game_status(&mut info);
-    let enemy = togatog_ai::solver::Solver::read_game_status(&mut info);
+    let current_turn: usize = info.read_turn().expect("invalid information file").expect("no turn");
+    let player = info.read_game_status().expect("invalid information file");
+    let enemy = info.read_game_status().expect("invalid information file");
+    solver.set_turn(current_turn);
     solver.set_game_status(player, enemy);
     solver.set_config(config);
     //measure
-    solver.think(current_turn)
+    solver.think()
 }
 
 
@@ -50,5 +53,108 @@ mod solver {
     }
 }
 
-criterion_group!(benches,solver::think);
+mod perft {
+    use super::*;
+    use criterion::Benchmark;
+    use togatog_ai::board::Board;
+
+    pub fn perft(c: &mut Criterion) {
+        c.bench("perft",
+                Benchmark::new("depth 3", |b| {
+                    //opened only when this benchmark runs, the checked in copy of pack_0000.pack
+                    let pack_file = File::open("input/test/pack_0000.pack").expect("can't open a file");
+                    let packs = ProtocolReader::new(BufReader::new(pack_file)).read_packs().expect("invalid pack file");
+                    b.iter(|| black_box(togatog_ai::perft::perft(&Board::default(), &packs, 0, 3)))
+                }).sample_size(10),
+        );
+    }
+}
+
+mod simulate {
+    use super::*;
+    use criterion::Benchmark;
+    use togatog_ai::bit_plane_board::BitPlaneBoard;
+    use togatog_ai::board::{Board, FIELD_HEIGHT};
+    use togatog_ai::pack::Pack;
+    use togatog_ai::reference_simulator::{random_pack, random_stable_grid, to_board};
+    use togatog_ai::simulator::Simulator;
+    use togatog_ai::xorshift::Xorshift;
+
+    //random stable boards with a drop which fits
+    fn cases() -> Vec<(Board, usize, Pack)> {
+        let mut rnd = Xorshift::with_seed(1);
+        let mut cases = Vec::new();
+        while cases.len() < 1000 {
+            let board = to_board(&random_stable_grid(&mut rnd, 12));
+            let pack = random_pack(&mut rnd).rotated(rnd.rand(4) as usize);
+            let point = rnd.rand(9) as usize;
+            if board.heights[point] + 2 <= FIELD_HEIGHT && board.heights[point + 1] + 2 <= FIELD_HEIGHT {
+                cases.push((board, point, pack));
+            }
+        }
+        cases
+    }
+
+    pub fn simulate(c: &mut Criterion) {
+        let cases = cases();
+        let converted_cases = cases.clone();
+        let bit_plane_cases: Vec<(BitPlaneBoard, usize, Pack)> = cases
+            .iter()
+            .map(|&(board, point, pack)| (BitPlaneBoard::from_board(&board), point, pack))
+            .collect();
+        c.bench("simulate",
+                Benchmark::new("simulator", move |b| {
+                    let mut simulator = Simulator::new();
+                    b.iter(|| {
+                        for &(board, point, pack) in cases.iter() {
+                            let mut board = board;
+                            black_box(simulator.simulate(&mut board, point, &pack));
+                        }
+                    })
+                }).with_function("bit plane board", move |b| b.iter(|| {
+                    for &(board, point, pack) in bit_plane_cases.iter() {
+                        let mut board = board;
+                        black_box(board.simulate(point, &pack));
+                    }
+                })).with_function("bit plane board from and to board", move |b| b.iter(|| {
+                    //what the solver would pay on its boards, slower than the simulator
+                    for &(board, point, pack) in converted_cases.iter() {
+                        let mut bit_plane_board = BitPlaneBoard::from_board(&board);
+                        black_box(bit_plane_board.simulate(point, &pack));
+                        black_box(bit_plane_board.to_board());
+                    }
+                })),
+        );
+    }
+}
+
+mod evaluate {
+    use super::*;
+    use criterion::Benchmark;
+    use togatog_ai::board::Board;
+    use togatog_ai::evaluation::EvaluateCache;
+    use togatog_ai::reference_simulator::{random_stable_grid, to_board};
+    use togatog_ai::simulator::Simulator;
+    use togatog_ai::xorshift::Xorshift;
+
+    //the estimates try dozens of erases and drops on every board
+    pub fn estimate(c: &mut Criterion) {
+        let mut rnd = Xorshift::with_seed(1);
+        let boards: Vec<Board> = (0..200).map(|_| to_board(&random_stable_grid(&mut rnd, 12))).collect();
+        c.bench("estimate",
+                Benchmark::new("max chain count and erasing all", move |b| {
+                    let mut simulator = Simulator::new();
+                    b.iter(|| {
+                        let mut evaluate_cache = EvaluateCache::new();
+                        for board in boards.iter() {
+                            black_box(evaluate_cache.estimate_max_chain_count(&mut simulator, board));
+                            black_box(evaluate_cache.estimate_with_erasing_all_max_chain_count(&mut simulator, board));
+                        }
+                    })
+                }),
+        );
+    }
+}
+
+criterion_group!(benches,solver::think,perft::perft,simulate::simulate,evaluate::estimate);
 criterion_main!(benches);
\ No newline at end of file
@@ -0,0 +1,61 @@
+//Obstacles the enemy is predicted to send during the search, by the depth of the player's search
+//they arrive at. An attack fired at depth d arrives at depth d + 1 like in the referee.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AttackSchedule {
+    obstacle_block_counts: Vec<u32>,
+}
+
+impl AttackSchedule {
+    pub fn new() -> AttackSchedule {
+        AttackSchedule { obstacle_block_counts: Vec::new() }
+    }
+    //obstacles sent by a fire at the depth
+    pub fn add_fire(&mut self, depth: usize, obstacle_block_count: u32) {
+        let arrival_depth = depth + 1;
+        if self.obstacle_block_counts.len() <= arrival_depth {
+            self.obstacle_block_counts.resize(arrival_depth + 1, 0);
+        }
+        self.obstacle_block_counts[arrival_depth] += obstacle_block_count;
+    }
+    //obstacles which arrive before the player drops at the depth
+    pub fn obstacle_block_count(&self, depth: usize) -> u32 {
+        self.obstacle_block_counts.get(depth).cloned().unwrap_or(0)
+    }
+    pub fn total_obstacle_block_count(&self) -> u32 {
+        self.obstacle_block_counts.iter().sum()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.total_obstacle_block_count() == 0
+    }
+    //arrival depths and obstacles of the attacks
+    pub fn iter(&self) -> impl Iterator<Item = (usize, u32)> + '_ {
+        self.obstacle_block_counts.iter().cloned().enumerate().filter(|&(_, count)| count > 0)
+    }
+}
+
+impl std::fmt::Display for AttackSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no attack");
+        }
+        let attacks: Vec<String> = self.iter().map(|(depth, count)| format!("{} at depth {}", count, depth)).collect();
+        write!(f, "{}", attacks.join(", "))
+    }
+}
+
+#[test]
+fn test_attack_schedule() {
+    let mut schedule = AttackSchedule::new();
+    assert!(schedule.is_empty());
+    assert_eq!(schedule.obstacle_block_count(3), 0);
+    schedule.add_fire(2, 33);
+    schedule.add_fire(0, 4);
+    schedule.add_fire(2, 1);
+    assert_eq!(schedule.obstacle_block_count(0), 0);
+    assert_eq!(schedule.obstacle_block_count(1), 4);
+    assert_eq!(schedule.obstacle_block_count(3), 34);
+    assert_eq!(schedule.obstacle_block_count(10), 0);
+    assert_eq!(schedule.total_obstacle_block_count(), 38);
+    assert_eq!(schedule.iter().collect::<Vec<_>>(), vec![(1, 4), (3, 34)]);
+    assert_eq!(schedule.to_string(), "4 at depth 1, 34 at depth 3");
+}
@@ -0,0 +1,125 @@
+extern crate min_max_heap;
+
+use self::min_max_heap::MinMaxHeap;
+use crate::search_state::SearchState;
+use std::cmp::Ordering;
+
+//a state's place in the arena ordered by its search score
+#[derive(Debug, Copy, Clone)]
+struct BeamEntry {
+    search_score: f64,
+    index: u32,
+}
+
+impl PartialEq for BeamEntry {
+    fn eq(&self, other: &BeamEntry) -> bool {
+        self.search_score == other.search_score
+    }
+}
+
+impl Eq for BeamEntry {}
+
+impl PartialOrd for BeamEntry {
+    fn partial_cmp(&self, other: &BeamEntry) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BeamEntry {
+    fn cmp(&self, other: &BeamEntry) -> Ordering {
+        self.search_score.partial_cmp(&other.search_score).unwrap()
+    }
+}
+
+//The states of one depth of the beam search. They stay in an arena and the heap only moves
+//scores and indices, slots of popped states are reused.
+pub struct Beam {
+    states: Vec<SearchState>,
+    free_indices: Vec<u32>,
+    heap: MinMaxHeap<BeamEntry>,
+}
+
+impl Default for Beam {
+    fn default() -> Beam {
+        Beam::new()
+    }
+}
+
+impl Beam {
+    pub fn new() -> Beam {
+        Beam { states: Vec::new(), free_indices: Vec::new(), heap: MinMaxHeap::new() }
+    }
+    pub fn with_capacity(capacity: usize) -> Beam {
+        Beam {
+            states: Vec::with_capacity(capacity),
+            free_indices: Vec::with_capacity(capacity),
+            heap: MinMaxHeap::with_capacity(capacity),
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+    pub fn push(&mut self, state: SearchState) {
+        let index = match self.free_indices.pop() {
+            Some(index) => {
+                self.states[index as usize] = state;
+                index
+            }
+            None => {
+                self.states.push(state);
+                (self.states.len() - 1) as u32
+            }
+        };
+        self.heap.push(BeamEntry { search_score: state.search_score(), index });
+    }
+    //push and drop the worst states beyond width, a state worse than all of a full beam isn't copied
+    pub fn push_bounded(&mut self, state: SearchState, width: usize) {
+        if self.len() >= width {
+            if let Some(min) = self.heap.peek_min() {
+                if state.search_score() < min.search_score {
+                    return;
+                }
+            }
+        }
+        self.push(state);
+        while self.len() > width {
+            self.pop_min();
+        }
+    }
+    fn take(&mut self, entry: Option<BeamEntry>) -> Option<SearchState> {
+        let entry = entry?;
+        self.free_indices.push(entry.index);
+        Some(self.states[entry.index as usize])
+    }
+    pub fn pop_max(&mut self) -> Option<SearchState> {
+        let entry = self.heap.pop_max();
+        self.take(entry)
+    }
+    pub fn pop_min(&mut self) -> Option<SearchState> {
+        let entry = self.heap.pop_min();
+        self.take(entry)
+    }
+}
+
+#[test]
+fn test_beam() {
+    let state = |search_score: f64| SearchState::default().with_search_score(search_score);
+    let mut beam = Beam::new();
+    for &search_score in [3.0, 1.0, 4.0, 1.5, 5.0, 9.0, 2.0].iter() {
+        beam.push_bounded(state(search_score), 4);
+        assert!(beam.len() <= 4);
+    }
+    //slots of dropped states are reused
+    assert_eq!(beam.states.len(), 5);
+    assert_eq!(beam.pop_max().map(|s| s.search_score()), Some(9.0));
+    assert_eq!(beam.pop_min().map(|s| s.search_score()), Some(3.0));
+    beam.push(state(7.0));
+    assert_eq!(beam.pop_max().map(|s| s.search_score()), Some(7.0));
+    assert_eq!(beam.pop_max().map(|s| s.search_score()), Some(5.0));
+    assert_eq!(beam.pop_max().map(|s| s.search_score()), Some(4.0));
+    assert!(beam.is_empty());
+    assert_eq!(beam.pop_max(), None);
+}
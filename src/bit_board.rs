@@ -70,6 +70,19 @@ impl BitBoard {
     pub fn zobrist_hash(&self) -> ZobristHash {
         self.zobrist_hash
     }
+    //the hash set() would have made from an empty board
+    pub fn calculate_zobrist_hash(&self) -> ZobristHash {
+        //a reference not to copy the table in debug builds
+        let table = &ZOBRIST_HASH_TABLE_BOARD;
+        let mut hash = 0;
+        for y in 0..FIELD_HEIGHT {
+            for x_idx in 0..BIT_FIELD_WIDTH {
+                hash ^= table[y][x_idx][0];
+                hash ^= table[y][x_idx][self.bits[y][x_idx] as usize];
+            }
+        }
+        hash
+    }
 }
 
 #[test]
@@ -118,6 +131,7 @@ fn test_zobrist_hash() {
     ];
     let board = BitBoard::new(board);
     debug_assert_eq!(bit_board.bits, board.bits);
+    assert_eq!(bit_board.zobrist_hash(), bit_board.calculate_zobrist_hash());
     debug_assert_eq!(bit_board.zobrist_hash, board.zobrist_hash);
 
 
@@ -0,0 +1,207 @@
+//A board with one bitplane per block kind. A plane has a u32 per column and bit y is the row y,
+//so erasing is shift-and-AND over complementary digits and gravity compacts the column bits.
+//An experiment, the simulator and the solver don't use it. simulate alone is about 2.3 times as
+//fast as Simulator::simulate, but converting a Board to it and back costs more than that saves.
+use crate::board::{Board, EMPTY_BLOCK, ERASING_SUM, FIELD_HEIGHT, FIELD_WIDTH, OBSTACLE_BLOCK};
+use crate::pack::Pack;
+use crate::simulator::SPELL_BLOCK;
+
+//planes 1-9 for the digits, 0 for obstacles
+const PLANE_COUNT: usize = 10;
+const OBSTACLE_PLANE: usize = 0;
+const ROW_MASK: u32 = (1 << FIELD_HEIGHT) - 1;
+
+type Plane = [u32; FIELD_WIDTH];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BitPlaneBoard {
+    planes: [Plane; PLANE_COUNT],
+}
+
+#[inline]
+fn plane_index(block: u8) -> usize {
+    debug_assert!(block != EMPTY_BLOCK && block != ERASING_SUM && block <= OBSTACLE_BLOCK);
+    if block == OBSTACLE_BLOCK {
+        OBSTACLE_PLANE
+    } else {
+        block as usize
+    }
+}
+
+//cells touching a block of the plane in 8 directions, the blocks themselves excluded
+#[inline]
+fn neighbors(plane: &Plane) -> Plane {
+    let mut vertical = [0; FIELD_WIDTH];
+    for x in 0..FIELD_WIDTH {
+        vertical[x] = plane[x] << 1 | plane[x] >> 1;
+    }
+    let mut res = [0; FIELD_WIDTH];
+    for x in 0..FIELD_WIDTH {
+        let mut mask = vertical[x];
+        if x > 0 {
+            mask |= plane[x - 1] | vertical[x - 1];
+        }
+        if x + 1 < FIELD_WIDTH {
+            mask |= plane[x + 1] | vertical[x + 1];
+        }
+        res[x] = mask & ROW_MASK;
+    }
+    res
+}
+
+//drops the bits above every erased bit by one, from the top so lower positions stay valid
+#[inline]
+fn compact(mut column: u32, mut erased: u32) -> u32 {
+    while erased != 0 {
+        let y = 31 - erased.leading_zeros();
+        let low = (1 << y) - 1;
+        column = (column & low) | ((column >> 1) & !low);
+        erased &= low;
+    }
+    column
+}
+
+impl BitPlaneBoard {
+    pub fn default() -> BitPlaneBoard {
+        BitPlaneBoard { planes: [[0; FIELD_WIDTH]; PLANE_COUNT] }
+    }
+    pub fn from_board(board: &Board) -> BitPlaneBoard {
+        let mut res = BitPlaneBoard::default();
+        for x in 0..FIELD_WIDTH {
+            for y in 0..board.heights[x] {
+                res.planes[plane_index(board.get(y, x))][x] |= 1 << y;
+            }
+        }
+        res
+    }
+    pub fn to_board(&self) -> Board {
+        let mut board = Board::default();
+        let heights = self.heights();
+        for (x, &height) in heights.iter().enumerate() {
+            for y in 0..height {
+                board.set(y, x, self.get(y, x));
+            }
+        }
+        board.heights = heights;
+        board
+    }
+    pub fn get(&self, y: usize, x: usize) -> u8 {
+        match self.planes.iter().position(|plane| plane[x] >> y & 1 == 1) {
+            Some(OBSTACLE_PLANE) => OBSTACLE_BLOCK,
+            Some(digit) => digit as u8,
+            None => EMPTY_BLOCK,
+        }
+    }
+    #[inline]
+    fn occupied(&self, x: usize) -> u32 {
+        self.planes.iter().fold(0, |occupied, plane| occupied | plane[x])
+    }
+    pub fn heights(&self) -> [usize; FIELD_WIDTH] {
+        let mut heights = [0; FIELD_WIDTH];
+        for (x, height) in heights.iter_mut().enumerate() {
+            *height = 32 - self.occupied(x).leading_zeros() as usize;
+        }
+        heights
+    }
+    pub fn drop_obstacles(&mut self) {
+        for x in 0..FIELD_WIDTH {
+            let occupied = self.occupied(x);
+            debug_assert!(occupied >> (FIELD_HEIGHT - 1) == 0);
+            self.planes[OBSTACLE_PLANE][x] |= occupied + 1;
+        }
+    }
+    //same as Simulator::simulate, the pack must fit
+    pub fn simulate(&mut self, point: usize, pack: &Pack) -> u8 {
+        debug_assert!(point <= 8);
+        for idx in (0..4).rev() {
+            let block = pack.get(idx);
+            if block == EMPTY_BLOCK {
+                continue;
+            }
+            let x = point + idx % 2;
+            //the lowest empty bit on a column without holes
+            let bit = self.occupied(x) + 1;
+            debug_assert!(bit & ROW_MASK != 0);
+            self.planes[plane_index(block)][x] |= bit;
+        }
+        self.calculate_chain_count()
+    }
+    //same as Simulator::spell, (erased block count, chain count)
+    pub fn spell(&mut self) -> (u32, u8) {
+        let spell_plane = self.planes[SPELL_BLOCK as usize];
+        let around = neighbors(&spell_plane);
+        let mut erased = [0; FIELD_WIDTH];
+        for x in 0..FIELD_WIDTH {
+            let numbers = self.occupied(x) & !self.planes[OBSTACLE_PLANE][x];
+            erased[x] = spell_plane[x] | (around[x] & numbers);
+        }
+        let erased_block_count: u32 = erased.iter().map(|column| column.count_ones()).sum();
+        if erased_block_count == 0 {
+            return (0, 0);
+        }
+        self.apply_erase_blocks(&erased);
+        (erased_block_count, self.calculate_chain_count())
+    }
+    //erases until nothing sums up to ERASING_SUM, the whole board is checked every time
+    pub fn calculate_chain_count(&mut self) -> u8 {
+        let mut chain_count = 0;
+        loop {
+            let erased = self.calculate_erase_blocks();
+            if erased.iter().all(|&column| column == 0) {
+                return chain_count;
+            }
+            chain_count += 1;
+            self.apply_erase_blocks(&erased);
+        }
+    }
+    fn calculate_erase_blocks(&self) -> Plane {
+        let mut erased = [0; FIELD_WIDTH];
+        for digit in 1..=(ERASING_SUM as usize / 2) {
+            let complement = ERASING_SUM as usize - digit;
+            let around = neighbors(&self.planes[complement]);
+            if digit == complement {
+                for x in 0..FIELD_WIDTH {
+                    erased[x] |= self.planes[digit][x] & around[x];
+                }
+                continue;
+            }
+            let digit_around = neighbors(&self.planes[digit]);
+            for x in 0..FIELD_WIDTH {
+                erased[x] |= self.planes[digit][x] & around[x];
+                erased[x] |= self.planes[complement][x] & digit_around[x];
+            }
+        }
+        erased
+    }
+    fn apply_erase_blocks(&mut self, erased: &Plane) {
+        for x in 0..FIELD_WIDTH {
+            if erased[x] == 0 {
+                continue;
+            }
+            for plane in self.planes.iter_mut() {
+                plane[x] = compact(plane[x] & !erased[x], erased[x]);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_bit_plane_board() {
+    let board = crate::test_fixtures::seven_next_to_three();
+    let mut bit_plane_board = BitPlaneBoard::from_board(&board);
+    assert_eq!(bit_plane_board.to_board(), board);
+    assert_eq!(bit_plane_board.heights(), board.heights);
+    assert_eq!(bit_plane_board.get(3, 5), 9);
+    assert_eq!(bit_plane_board.get(0, 0), OBSTACLE_BLOCK);
+
+    //7 erases with 3, then 9 falls next to 1
+    assert_eq!(bit_plane_board.simulate(6, &Pack::new(&[7, 0, 0, 0])), 2);
+    assert_eq!(bit_plane_board.heights(), [1, 0, 0, 0, 1, 2, 1, 0, 0, 0]);
+    assert_eq!((bit_plane_board.get(0, 4), bit_plane_board.get(1, 5)), (4, 2));
+
+    bit_plane_board.drop_obstacles();
+    assert_eq!(bit_plane_board.heights(), [2, 1, 1, 1, 2, 3, 2, 1, 1, 1]);
+    assert_eq!(bit_plane_board.to_board().check_invariants(), Ok(()));
+
+    assert_eq!(compact(0b1011_0110, 0b0010_0100), 0b10_1010);
+}
@@ -8,8 +8,10 @@ pub const ERASING_SUM: u8 = 10;
 
 
 use crate::bit_board::BitBoard;
+use crate::simulator::Simulator;
 use crate::zobrist_hash_table::ZobristHash;
 use std::fmt;
+use std::str::FromStr;
 
 #[derive(Debug, Copy, Clone, Eq, Hash)]
 pub struct Board {
@@ -26,22 +28,235 @@ impl PartialEq for Board {
     }
 }
 impl fmt::Display for Board {
+    //{:#} also prints the hidden rows
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut board = vec![];
-        for y in 0..FIELD_HEIGHT {
-            let mut tmp = Vec::new();
-            for x in 0..FIELD_WIDTH {
-                tmp.push(format!("{:?}", self.board.get(y, x)));
+        self.display().with_hidden_rows(f.alternate()).fmt(f)
+    }
+}
+
+impl FromStr for Board {
+    type Err = ParseBoardError;
+    //top-down rows, '.' or '0' for empty, '1'-'9' and '#' for obstacle
+    //16 rows for the visible field or 19 rows with the hidden rows
+    fn from_str(s: &str) -> Result<Board, ParseBoardError> {
+        let lines: Vec<(usize, Vec<char>)> = s
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.chars().filter(|c| !c.is_whitespace()).collect()))
+            .filter(|(_, cells): &(usize, Vec<char>)| !cells.is_empty())
+            .collect();
+        if lines.len() != INPUT_FIELD_HEIGHT && lines.len() != FIELD_HEIGHT {
+            return Err(ParseBoardError::RowCount(lines.len()));
+        }
+        let mut board = Board::default();
+        for (row, (line, cells)) in lines.iter().enumerate() {
+            if cells.len() != FIELD_WIDTH {
+                return Err(ParseBoardError::ColumnCount(*line, cells.len()));
+            }
+            let y = lines.len() - 1 - row;
+            for (x, &c) in cells.iter().enumerate() {
+                let block = match c {
+                    '.' | '0' => EMPTY_BLOCK,
+                    '#' => OBSTACLE_BLOCK,
+                    '1'..='9' => c as u8 - b'0',
+                    _ => return Err(ParseBoardError::InvalidCell(*line, c)),
+                };
+                board.set(y, x, block);
+            }
+        }
+        for x in 0..FIELD_WIDTH {
+            let mut y = 0;
+            while y < FIELD_HEIGHT && board.get(y, x) != EMPTY_BLOCK {
+                y += 1;
+            }
+            board.heights[x] = y;
+            if let Some(y) = (y..FIELD_HEIGHT).find(|&y| board.get(y, x) != EMPTY_BLOCK) {
+                return Err(ParseBoardError::FloatingBlock(y, x));
+            }
+        }
+        Ok(board)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseBoardError {
+    RowCount(usize),
+    //(line, cell count)
+    ColumnCount(usize, usize),
+    //(line, character)
+    InvalidCell(usize, char),
+    //(y, x)
+    FloatingBlock(usize, usize),
+}
+
+impl fmt::Display for ParseBoardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseBoardError::RowCount(rows) => write!(
+                f,
+                "expected {} or {} rows but got {}",
+                INPUT_FIELD_HEIGHT, FIELD_HEIGHT, rows
+            ),
+            ParseBoardError::ColumnCount(line, cells) => write!(
+                f,
+                "line {}: expected {} cells but got {}",
+                line, FIELD_WIDTH, cells
+            ),
+            ParseBoardError::InvalidCell(line, c) => {
+                write!(f, "line {}: invalid cell {:?}", line, c)
+            }
+            ParseBoardError::FloatingBlock(y, x) => {
+                write!(f, "floating block at y: {}, x: {}", y, x)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseBoardError {}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum InvariantViolation {
+    //(x, height, height from the cells)
+    Height(usize, usize, usize),
+    //(y, x)
+    FloatingBlock(usize, usize),
+    //(y, x, block)
+    InvalidBlock(usize, usize, u8),
+    //(hash, hash from the cells)
+    ZobristHash(ZobristHash, ZobristHash),
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvariantViolation::Height(x, height, expected) => write!(
+                f,
+                "height of x: {} is {} but the cells say {}",
+                x, height, expected
+            ),
+            InvariantViolation::FloatingBlock(y, x) => {
+                write!(f, "floating block at y: {}, x: {}", y, x)
+            }
+            InvariantViolation::InvalidBlock(y, x, block) => {
+                write!(f, "invalid block {} at y: {}, x: {}", block, y, x)
+            }
+            InvariantViolation::ZobristHash(hash, expected) => {
+                write!(f, "zobrist hash is {} but the cells say {}", hash, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvariantViolation {}
+
+//Board with u8 heights for the states the beam keeps by the thousand
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct CompactBoard {
+    board: BitBoard,
+    heights: [u8; FIELD_WIDTH],
+}
+
+impl CompactBoard {
+    pub fn default() -> CompactBoard {
+        CompactBoard::from_board(&Board::default())
+    }
+    pub fn from_board(board: &Board) -> CompactBoard {
+        let mut heights = [0; FIELD_WIDTH];
+        for (height, &board_height) in heights.iter_mut().zip(board.heights.iter()) {
+            *height = board_height as u8;
+        }
+        CompactBoard { board: board.board, heights }
+    }
+    #[inline]
+    pub fn to_board(&self) -> Board {
+        let mut heights = [0; FIELD_WIDTH];
+        for (height, &compact_height) in heights.iter_mut().zip(self.heights.iter()) {
+            *height = compact_height as usize;
+        }
+        Board { board: self.board, heights }
+    }
+    #[inline]
+    pub fn zobrist_hash(&self) -> ZobristHash {
+        self.board.zobrist_hash()
+    }
+}
+
+//what Board::new_with_gravity found in its input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputStability {
+    //blocks which were floating
+    pub fallen_block_count: usize,
+    //chains which happen once the blocks have fallen
+    pub pending_chain_count: u8,
+}
+
+impl InputStability {
+    pub fn is_stable(&self) -> bool {
+        self.fallen_block_count == 0 && self.pending_chain_count == 0
+    }
+}
+
+//ANSI colours for blocks 0..=11
+const BLOCK_COLORS: [&str; 12] = [
+    "\x1b[2m", "\x1b[31m", "\x1b[32m", "\x1b[33m", "\x1b[34m", "\x1b[35m", "\x1b[36m",
+    "\x1b[94m", "\x1b[93m", "\x1b[92m", "", "\x1b[90m",
+];
+const RESET_COLOR: &str = "\x1b[0m";
+
+pub fn block_char(block: u8) -> char {
+    match block {
+        EMPTY_BLOCK => '.',
+        OBSTACLE_BLOCK => '#',
+        _ => (b'0' + block) as char,
+    }
+}
+
+pub struct BoardDisplay<'a> {
+    board: &'a Board,
+    hidden_rows: bool,
+    color: bool,
+}
+
+impl<'a> BoardDisplay<'a> {
+    pub fn with_hidden_rows(mut self, hidden_rows: bool) -> Self {
+        self.hidden_rows = hidden_rows;
+        self
+    }
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+    pub fn rows(&self) -> usize {
+        if self.hidden_rows {
+            FIELD_HEIGHT
+        } else {
+            INPUT_FIELD_HEIGHT
+        }
+    }
+    //one rendered row, counted from the top
+    pub fn row(&self, row: usize) -> String {
+        let y = self.rows() - 1 - row;
+        let mut line = String::new();
+        for x in 0..FIELD_WIDTH {
+            let block = self.board.get(y, x);
+            if self.color {
+                line.push_str(BLOCK_COLORS[block as usize]);
+                line.push(block_char(block));
+                line.push_str(RESET_COLOR);
+            } else {
+                line.push(block_char(block));
             }
-            debug_assert_eq!(tmp.len(), FIELD_WIDTH);
-            board.push(tmp.join(","));
         }
-        write!(
-            f,
-            "Board {{ board: [{}], heights: [{:?}] }}",
-            board.join(",\n"),
-            self.heights
-        )
+        line
+    }
+}
+
+impl<'a> fmt::Display for BoardDisplay<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in 0..self.rows() {
+            writeln!(f, "{}", self.row(row))?;
+        }
+        Ok(())
     }
 }
 
@@ -53,6 +268,7 @@ impl Board {
             heights: [0; FIELD_WIDTH],
         }
     }
+    //the input must not have holes, see new_with_gravity for boards which may
     pub fn new(input_board: [[u8; FIELD_WIDTH]; INPUT_FIELD_HEIGHT]) -> Board {
         let mut heights: [usize; FIELD_WIDTH] = [0; FIELD_WIDTH];
         for x in 0..FIELD_WIDTH {
@@ -68,6 +284,39 @@ impl Board {
         let board = BitBoard::new(input_board);
         Board { board, heights }
     }
+    //drops floating blocks to the bottom, and resolves the pending chains if resolve_chains
+    //the stability tells what was wrong with the input either way
+    pub fn new_with_gravity(
+        input_board: [[u8; FIELD_WIDTH]; INPUT_FIELD_HEIGHT],
+        resolve_chains: bool,
+    ) -> (Board, InputStability) {
+        let mut compacted = [[EMPTY_BLOCK; FIELD_WIDTH]; INPUT_FIELD_HEIGHT];
+        let mut fallen_block_count = 0;
+        for x in 0..FIELD_WIDTH {
+            let mut ny = 0;
+            for y in 0..INPUT_FIELD_HEIGHT {
+                let block = input_board[INPUT_FIELD_HEIGHT - 1 - y][x];
+                if block == EMPTY_BLOCK {
+                    continue;
+                }
+                if ny != y {
+                    fallen_block_count += 1;
+                }
+                compacted[INPUT_FIELD_HEIGHT - 1 - ny][x] = block;
+                ny += 1;
+            }
+        }
+        let mut board = Board::new(compacted);
+        let mut resolved = board;
+        let pending_chain_count = Simulator::new().resolve_chains(&mut resolved);
+        if resolve_chains {
+            board = resolved;
+        }
+        (board, InputStability { fallen_block_count, pending_chain_count })
+    }
+    pub fn display(&self) -> BoardDisplay<'_> {
+        BoardDisplay { board: self, hidden_rows: false, color: false }
+    }
     #[inline]
     pub fn get(&self, y: usize, x: usize) -> u8 {
         debug_assert!(y < FIELD_HEIGHT);
@@ -87,6 +336,43 @@ impl Board {
         self.board.zobrist_hash()
     }
 
+    //heights agree with the cells, nothing floats and the zobrist hash is up to date
+    pub fn check_invariants(&self) -> Result<(), InvariantViolation> {
+        for x in 0..FIELD_WIDTH {
+            let mut expected = 0;
+            for y in 0..FIELD_HEIGHT {
+                let block = self.board.get(y, x);
+                if block > OBSTACLE_BLOCK || block == ERASING_SUM {
+                    return Err(InvariantViolation::InvalidBlock(y, x, block));
+                }
+                if block != EMPTY_BLOCK {
+                    expected = y + 1;
+                }
+            }
+            if self.heights[x] != expected {
+                return Err(InvariantViolation::Height(x, self.heights[x], expected));
+            }
+            if let Some(hole) = (0..expected).find(|&y| self.board.get(y, x) == EMPTY_BLOCK) {
+                let y = (hole..expected).find(|&y| self.board.get(y, x) != EMPTY_BLOCK).unwrap();
+                return Err(InvariantViolation::FloatingBlock(y, x));
+            }
+        }
+        let expected = self.board.calculate_zobrist_hash();
+        if self.board.zobrist_hash() != expected {
+            return Err(InvariantViolation::ZobristHash(self.board.zobrist_hash(), expected));
+        }
+        Ok(())
+    }
+    //panics on a broken board in debug builds
+    #[inline]
+    pub fn debug_check_invariants(&self) {
+        #[cfg(debug_assertions)]
+        {
+            if let Err(e) = self.check_invariants() {
+                panic!("{}\n{:#}", e, self);
+            }
+        }
+    }
     pub fn drop_obstacles(&mut self) {
         for x in 0..FIELD_WIDTH {
             debug_assert!(self.heights[x] < FIELD_HEIGHT);
@@ -239,4 +525,189 @@ fn drop_obstacles() {
     ];
     let dropped_obstacles_board = Board::new(dropped_obstacles_board);
     debug_assert_eq!(board, dropped_obstacles_board);
-}
\ No newline at end of file
+}
+#[test]
+fn test_from_str() {
+    let board = [
+        [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 2, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 4, 0, 0, 0, 0, 0],
+        [0, 0, 0, 7, 4, 0, 0, 0, 0, 0],
+        [0, 0, 0, 4, 4, 8, 0, 0, 0, 0],
+        [0, 0, 0, 9, 8, 4, 0, 0, 0, 0],
+        [0, 0, 0, 3, 4, 8, 9, 0, 0, 0],
+        [0, 0, 0, 5, 9, 4, 8, 0, 0, 0],
+        [0, 0, 1, 6, 3, 4, 1, 0, 11, 0],
+        [0, 0, 6, 5, 1, 2, 3, 4, 11, 0],
+        [0, 0, 1, 3, 6, 2, 2, 1, 11, 0],
+    ];
+    let text = "
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ....2.....
+        ....4.....
+        ...74.....
+        ...448....
+        ...984....
+        ...3489...
+        ...5948...
+        ..16341.#.
+        ..651234#.
+        ..136221#.
+    ";
+    let parsed: Board = text.parse().unwrap();
+    assert_eq!(parsed, Board::new(board));
+    assert_eq!(parsed.heights, Board::new(board).heights);
+    //round trip
+    assert_eq!(parsed.to_string().parse::<Board>(), Ok(parsed));
+    assert_eq!(format!("{:#}", parsed).parse::<Board>(), Ok(parsed));
+    assert_eq!(format!("{:#}", parsed).lines().count(), FIELD_HEIGHT);
+    assert_eq!(parsed.to_string().lines().next(), Some(".........."));
+    assert_eq!(parsed.to_string().lines().last(), Some("..136221#."));
+    //a row has too many cells
+    let text = text.replace("..136221#.", "..1362221#.");
+    assert_eq!(text.parse::<Board>(), Err(ParseBoardError::ColumnCount(17, 11)));
+}
+
+#[test]
+fn test_from_str_hidden_rows() {
+    let mut board = Board::default();
+    for y in 0..FIELD_HEIGHT {
+        board.set(y, 3, OBSTACLE_BLOCK);
+    }
+    board.heights[3] = FIELD_HEIGHT;
+    board.set(0, 0, 5);
+    board.heights[0] = 1;
+    let text = format!("{:#}", board);
+    assert_eq!(text.lines().next(), Some("...#......"));
+    let parsed: Board = text.parse().unwrap();
+    assert_eq!(parsed, board);
+    assert_eq!(parsed.heights, board.heights);
+    //the hidden rows are not printed by default
+    assert_eq!(board.to_string().lines().count(), INPUT_FIELD_HEIGHT);
+}
+
+#[test]
+fn test_from_str_error() {
+    assert_eq!("..........".parse::<Board>(), Err(ParseBoardError::RowCount(1)));
+    let mut rows = vec![".........."; INPUT_FIELD_HEIGHT];
+    rows[15] = "...x......";
+    assert_eq!(rows.join("\n").parse::<Board>(), Err(ParseBoardError::InvalidCell(16, 'x')));
+    rows[15] = "...#......";
+    rows[10] = "...1......";
+    assert_eq!(rows.join("\n").parse::<Board>(), Err(ParseBoardError::FloatingBlock(5, 3)));
+}
+
+#[test]
+fn test_display_with_color() {
+    let mut board = Board::default();
+    board.set(0, 0, 1);
+    board.set(0, 1, OBSTACLE_BLOCK);
+    board.heights[0] = 1;
+    board.heights[1] = 1;
+    let colored = board.display().with_color(true).to_string();
+    assert!(colored.lines().last().unwrap().starts_with("\x1b[31m1\x1b[0m\x1b[90m#\x1b[0m"));
+    assert_eq!(board.display().row(INPUT_FIELD_HEIGHT - 1), "1#........");
+}
+
+#[test]
+fn test_check_invariants() {
+    let mut board: Board = "
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        .....3....
+        .....2....
+        ##...1....
+    "
+    .parse()
+    .unwrap();
+    assert_eq!(board.check_invariants(), Ok(()));
+    board.drop_obstacles();
+    assert_eq!(board.check_invariants(), Ok(()));
+
+    let mut broken = board;
+    broken.set(5, 0, 4);
+    assert_eq!(broken.check_invariants(), Err(InvariantViolation::Height(0, 2, 6)));
+    broken.heights[0] = 6;
+    assert_eq!(broken.check_invariants(), Err(InvariantViolation::FloatingBlock(5, 0)));
+
+    let mut broken = board;
+    broken.set(0, 5, EMPTY_BLOCK);
+    assert_eq!(broken.check_invariants(), Err(InvariantViolation::FloatingBlock(1, 5)));
+}
+
+#[test]
+fn test_compact_board() {
+    assert!(std::mem::size_of::<CompactBoard>() < std::mem::size_of::<Board>());
+    let mut board = Board::default();
+    board.drop_obstacles();
+    board.set(1, 3, 7);
+    board.heights[3] = 2;
+    let compact_board = CompactBoard::from_board(&board);
+    assert_eq!(compact_board.zobrist_hash(), board.zobrist_hash());
+    assert_eq!(compact_board.to_board(), board);
+    assert_eq!(compact_board.to_board().heights, board.heights);
+}
+
+#[test]
+fn test_new_with_gravity() {
+    let mut input_board = [[EMPTY_BLOCK; FIELD_WIDTH]; INPUT_FIELD_HEIGHT];
+    //1 floats over 9 at x = 0, 3 and 7 float over a hole at x = 2
+    input_board[15][0] = 9;
+    input_board[10][0] = 1;
+    input_board[15][2] = 4;
+    input_board[12][2] = 3;
+    input_board[11][2] = 7;
+    input_board[15][5] = OBSTACLE_BLOCK;
+
+    let (board, stability) = Board::new_with_gravity(input_board, false);
+    assert_eq!(board.check_invariants(), Ok(()));
+    assert_eq!(board.heights, [2, 0, 3, 0, 0, 1, 0, 0, 0, 0]);
+    assert_eq!((board.get(1, 0), board.get(1, 2), board.get(2, 2)), (1, 3, 7));
+    assert_eq!(stability, InputStability { fallen_block_count: 3, pending_chain_count: 1 });
+    assert!(!stability.is_stable());
+
+    let (resolved, stability) = Board::new_with_gravity(input_board, true);
+    assert_eq!(resolved.check_invariants(), Ok(()));
+    //9 and 1, 3 and 7 erase at once
+    assert_eq!(resolved.heights, [0, 0, 1, 0, 0, 1, 0, 0, 0, 0]);
+    assert_eq!(stability.pending_chain_count, 1);
+
+    //a stable input is kept as is
+    let (stable, stability) = Board::new_with_gravity(
+        [[EMPTY_BLOCK; FIELD_WIDTH]; INPUT_FIELD_HEIGHT],
+        true,
+    );
+    assert_eq!(stable, Board::default());
+    assert!(stability.is_stable());
+    let mut stable_input = [[EMPTY_BLOCK; FIELD_WIDTH]; INPUT_FIELD_HEIGHT];
+    for (row, blocks) in stable_input.iter_mut().enumerate() {
+        for (x, block) in blocks.iter_mut().enumerate() {
+            *block = resolved.get(INPUT_FIELD_HEIGHT - 1 - row, x);
+        }
+    }
+    let (same, stability) = Board::new_with_gravity(stable_input, false);
+    assert_eq!(same, resolved);
+    assert_eq!(same, Board::new(stable_input));
+    assert!(stability.is_stable());
+}
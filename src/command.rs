@@ -1,3 +1,5 @@
+use std::fmt;
+use std::str::FromStr;
 
 #[derive(Debug, Copy, Clone, Eq)]
 pub enum Command {
@@ -19,4 +21,47 @@ impl PartialEq for Command {
             _ => false
         }
     }
-}
\ No newline at end of file
+}
+
+//the same format as the game protocol
+//"point rotate_count" or "S"
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::Drop((point, rotate_count)) => write!(f, "{} {}", point, rotate_count),
+            Command::Spell => write!(f, "S"),
+        }
+    }
+}
+
+impl FromStr for Command {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Command, String> {
+        let tokens: Vec<&str> = s.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["S"] => Ok(Command::Spell),
+            [point, rotate_count] => {
+                let point: usize = point.parse().map_err(|_| format!("invalid point: {}", s))?;
+                let rotate_count: usize = rotate_count
+                    .parse()
+                    .map_err(|_| format!("invalid rotate count: {}", s))?;
+                if point > 8 || rotate_count > 3 {
+                    return Err(format!("command out of range: {}", s));
+                }
+                Ok(Command::Drop((point, rotate_count)))
+            }
+            _ => Err(format!("invalid command: {}", s)),
+        }
+    }
+}
+
+#[test]
+fn test_command_from_str() {
+    assert_eq!("3 1".parse::<Command>(), Ok(Command::Drop((3, 1))));
+    assert_eq!(" S ".parse::<Command>(), Ok(Command::Spell));
+    assert!("9 0".parse::<Command>().is_err());
+    assert!("1 4".parse::<Command>().is_err());
+    assert!("togatog_ai_4.0".parse::<Command>().is_err());
+    assert_eq!(Command::Drop((8, 3)).to_string().parse::<Command>(), Ok(Command::Drop((8, 3))));
+    assert_eq!(Command::Spell.to_string(), "S");
+}
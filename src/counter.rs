@@ -0,0 +1,303 @@
+//A short defensive beam for when obstacles are coming at the player. Plans are sorted into taking
+//the obstacles and building on (absorb), firing the smallest chain which cancels them (offset), or
+//firing a chain whose obstacles the enemy can't outlast by the lethal check (counter kill).
+use crate::beam::Beam;
+use crate::board::{Board, FIELD_WIDTH};
+use crate::command::Command;
+use crate::evaluation::EvaluateCache;
+use crate::game_status::GameStatus;
+use crate::lethal::LethalCheck;
+use crate::pack::Pack;
+use crate::referee::calculate_skill_reduce;
+use crate::search_state::SearchState;
+use crate::simulator;
+use crate::simulator::Simulator;
+use crate::solver::{principal_variation, Transition};
+use crate::transposition::TranspositionSet;
+use crate::xorshift::Xorshift;
+
+pub const DEFAULT_COUNTER_BEAM_DEPTH: usize = 3;
+pub const DEFAULT_COUNTER_BEAM_WIDTH: usize = 200;
+//a lethal check for every candidate kill, smaller than the one before a fire
+pub const DEFAULT_COUNTER_LETHAL_NODE_LIMIT: usize = 2_000;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CounterAction {
+    Absorb,
+    Offset,
+    CounterKill,
+}
+
+impl std::fmt::Display for CounterAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CounterAction::Absorb => write!(f, "absorb"),
+            CounterAction::Offset => write!(f, "offset"),
+            CounterAction::CounterKill => write!(f, "counter kill"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CounterPlan {
+    pub action: CounterAction,
+    //commands from this turn, the last one fires unless the plan absorbs
+    pub principal_variation: Vec<Command>,
+    pub chain_count: u8,
+    //obstacles the player's board can still send after the plan
+    pub counter_obstacle_block_count: u32,
+    //obstacles the plan sends to the enemy
+    pub sent_obstacle_block_count: u32,
+    pub board: Board,
+}
+
+impl CounterPlan {
+    pub fn command(&self) -> Command {
+        self.principal_variation[0]
+    }
+    pub fn depth(&self) -> usize {
+        self.principal_variation.len() - 1
+    }
+}
+
+//drops whole lines of obstacles, false if the board dies
+pub fn drop_obstacle_lines(board: &mut Board, obstacle_block_count: u32) -> bool {
+    for _ in 0..obstacle_block_count / FIELD_WIDTH as u32 {
+        if board.is_game_over() {
+            return false;
+        }
+        board.drop_obstacles();
+    }
+    !board.is_game_over()
+}
+
+pub struct CounterSearch {
+    beam_depth: usize,
+    beam_width: usize,
+    lethal_node_limit: usize,
+    seed: u64,
+}
+
+impl CounterSearch {
+    pub fn default() -> CounterSearch {
+        CounterSearch {
+            beam_depth: DEFAULT_COUNTER_BEAM_DEPTH,
+            beam_width: DEFAULT_COUNTER_BEAM_WIDTH,
+            lethal_node_limit: DEFAULT_COUNTER_LETHAL_NODE_LIMIT,
+            seed: 1024,
+        }
+    }
+    pub fn with_beam(mut self, beam_depth: usize, beam_width: usize) -> Self {
+        self.beam_depth = beam_depth;
+        self.beam_width = beam_width;
+        self
+    }
+    pub fn with_lethal_node_limit(mut self, lethal_node_limit: usize) -> Self {
+        self.lethal_node_limit = lethal_node_limit;
+        self
+    }
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+    //the chain the board can still fire, in obstacles
+    fn counter_obstacle_block_count(simulator: &mut Simulator, evaluate_cache: &mut EvaluateCache, board: &Board) -> u32 {
+        let (chain_count, _) = evaluate_cache.estimate_with_erasing_all_max_chain_count(simulator, board);
+        simulator::calculate_obstacle_count_from_chain_count(chain_count)
+    }
+    //the best plan of every action from simulated outcomes, empty without a line coming
+    pub fn search(
+        &self,
+        simulator: &mut Simulator,
+        evaluate_cache: &mut EvaluateCache,
+        packs: &[Vec<(Pack, usize)>],
+        turn: usize,
+        player: &GameStatus,
+        enemy: &GameStatus,
+    ) -> Vec<CounterPlan> {
+        if player.obstacle_block_count() < FIELD_WIDTH as u32 {
+            return Vec::new();
+        }
+        let mut lethal_check = LethalCheck::default().with_node_limit(self.lethal_node_limit);
+        let mut beams: Vec<Beam> = (0..self.beam_depth + 1).map(|_| Beam::with_capacity(self.beam_width + 1)).collect();
+        let root = SearchState::default()
+            .with_board(player.board())
+            .with_obstacle_block_count(player.obstacle_block_count())
+            .with_spawn_obstacle_block_count(enemy.obstacle_block_count())
+            .with_cumulative_game_score(player.cumulative_game_score());
+        beams[0].push(root);
+        let mut rnd = Xorshift::with_seed(turn as u64 + self.seed);
+        let mut searched_state = TranspositionSet::new();
+        let mut transitions = vec![Transition::root()];
+        //a plan and its score for each action
+        let mut best: [Option<(i64, CounterPlan)>; 3] = [None, None, None];
+        let mut children = Vec::with_capacity(4 * 9);
+        for depth in 0..self.beam_depth {
+            let search_turn = turn + depth;
+            if search_turn >= packs.len() {
+                break;
+            }
+            while let Some(mut search_state) = beams[depth].pop_max() {
+                search_state.update_obstacle_block_and_drop();
+                search_state.board().debug_check_invariants();
+                children.clear();
+                children.extend(simulator.expand(&search_state.board(), &packs[search_turn]));
+                for child in children.iter() {
+                    if child.board.is_game_over() {
+                        continue;
+                    }
+                    let mut next_search_state = search_state.after_drop(child);
+                    if !searched_state.insert(&next_search_state) {
+                        continue;
+                    }
+                    transitions.push(Transition::from_child(search_state.history(), child));
+                    next_search_state.set_history((transitions.len() - 1) as u32);
+                    let fired = next_search_state.cumulative_game_score() > player.cumulative_game_score();
+                    let (action, score, plan) = if fired {
+                        //the chain has to leave less than a line
+                        if next_search_state.obstacle_block_count() >= FIELD_WIDTH as u32 {
+                            continue;
+                        }
+                        //only absorbing states are searched on, so the parent never fired and the
+                        //increase is what this chain sends, the enemy's pending obstacles aside
+                        let sent = next_search_state
+                            .spawn_obstacle_block_count()
+                            .saturating_sub(search_state.spawn_obstacle_block_count());
+                        let kill_score = -(depth as i64) * 1000 + sent as i64;
+                        //the enemy's current board stands in for its board at the depth, a kill which
+                        //can't beat the best one isn't checked
+                        let kill = sent + enemy.obstacle_block_count() >= FIELD_WIDTH as u32
+                            && best[CounterAction::CounterKill as usize]
+                                .as_ref()
+                                .is_none_or(|(best_score, _)| kill_score > *best_score)
+                            && lethal_check
+                                .check(
+                                    simulator,
+                                    packs,
+                                    search_turn,
+                                    enemy,
+                                    sent,
+                                    calculate_skill_reduce(child.chain_count),
+                                )
+                                .is_lethal();
+                        //a fire with no line left to cancel only matters if it kills
+                        if !kill && search_state.obstacle_block_count() < FIELD_WIDTH as u32 {
+                            continue;
+                        }
+                        let counter = CounterSearch::counter_obstacle_block_count(simulator, evaluate_cache, &child.board);
+                        let plan = CounterPlan {
+                            action: if kill { CounterAction::CounterKill } else { CounterAction::Offset },
+                            principal_variation: principal_variation(&transitions, next_search_state.history()),
+                            chain_count: child.chain_count,
+                            counter_obstacle_block_count: counter,
+                            sent_obstacle_block_count: sent,
+                            board: child.board,
+                        };
+                        //the sooner the better, then the smaller chain which keeps more to counter with
+                        let score = if kill {
+                            kill_score
+                        } else {
+                            -(depth as i64) * 1000 + counter as i64 - child.chain_count as i64
+                        };
+                        (plan.action, score, plan)
+                    } else {
+                        //the board after taking every obstacle left
+                        let mut board = child.board;
+                        if !drop_obstacle_lines(&mut board, next_search_state.obstacle_block_count()) {
+                            continue;
+                        }
+                        let counter = CounterSearch::counter_obstacle_block_count(simulator, evaluate_cache, &board);
+                        let plan = CounterPlan {
+                            action: CounterAction::Absorb,
+                            principal_variation: principal_variation(&transitions, next_search_state.history()),
+                            chain_count: 0,
+                            counter_obstacle_block_count: counter,
+                            sent_obstacle_block_count: 0,
+                            board,
+                        };
+                        next_search_state.set_search_score(
+                            evaluate_cache.evaluate_search_score(simulator, &next_search_state) + rnd.randf(),
+                        );
+                        beams[depth + 1].push_bounded(next_search_state, self.beam_width);
+                        (CounterAction::Absorb, counter as i64 * 1000 - depth as i64, plan)
+                    };
+                    let slot = &mut best[action as usize];
+                    if slot.as_ref().is_none_or(|(best_score, _)| score > *best_score) {
+                        *slot = Some((score, plan));
+                    }
+                }
+            }
+        }
+        best.iter().flatten().map(|(_, plan)| plan.clone()).collect()
+    }
+    //a counter kill if there is one, otherwise offsetting or absorbing, whichever keeps more to
+    //counter with; absorbing has to survive every obstacle
+    pub fn choose(plans: &[CounterPlan]) -> Option<&CounterPlan> {
+        let find = |action: CounterAction| plans.iter().find(|plan| plan.action == action);
+        if let Some(plan) = find(CounterAction::CounterKill) {
+            return Some(plan);
+        }
+        match (find(CounterAction::Offset), find(CounterAction::Absorb)) {
+            (Some(offset), Some(absorb)) => {
+                if absorb.counter_obstacle_block_count > offset.counter_obstacle_block_count {
+                    Some(absorb)
+                } else {
+                    Some(offset)
+                }
+            }
+            (offset, absorb) => offset.or(absorb),
+        }
+    }
+}
+
+#[test]
+fn test_counter_search() {
+    //7 next to 3 makes 2 chains
+    let player = crate::test_fixtures::seven_next_to_three();
+    let packs = vec![Pack::new(&[7, 0, 0, 0]).unique_rotations(); 10];
+    let mut simulator = Simulator::new();
+    let mut evaluate_cache = EvaluateCache::with_capacity_bytes(1 << 20);
+    let search = CounterSearch::default().with_beam(2, 50);
+    let enemy = GameStatus::default();
+    //nothing to counter
+    let player = GameStatus::default().with_board(player);
+    assert!(search.search(&mut simulator, &mut evaluate_cache, &packs, 0, &player, &enemy).is_empty());
+
+    //a line drops first, the 2 chains send 1 obstacle and leave 9 which is less than a line
+    let player = player.with_obstacle_block_count(20);
+    let plans = search.search(&mut simulator, &mut evaluate_cache, &packs, 0, &player, &enemy);
+    let offset = plans.iter().find(|plan| plan.action == CounterAction::Offset).unwrap();
+    assert_eq!((offset.depth(), offset.chain_count, offset.sent_obstacle_block_count), (0, 2, 0));
+    //taking both lines
+    let absorb = plans.iter().find(|plan| plan.action == CounterAction::Absorb).unwrap();
+    assert_eq!(absorb.board.count_blocks().1, 1 + 20);
+    assert!(plans.iter().all(|plan| plan.action != CounterAction::CounterKill));
+    assert!(CounterSearch::choose(&plans).is_some());
+
+    //9 chains after the line drops, the enemy a line from the top dies to the next line
+    let player = crate::test_fixtures::nine_chains();
+    let packs = vec![Pack::new(&[0, 7, 5, 6]).unique_rotations(); 10];
+    let player = GameStatus::default().with_board(player).with_obstacle_block_count(10);
+    let enemy = GameStatus::default().with_board(crate::test_fixtures::wall());
+    let plans = search.search(&mut simulator, &mut evaluate_cache, &packs, 0, &player, &enemy);
+    let kill = CounterSearch::choose(&plans).unwrap();
+    let sent = simulator::calculate_obstacle_count_from_chain_count(9);
+    assert_eq!((kill.action, kill.depth(), kill.chain_count), (CounterAction::CounterKill, 0, 9));
+    assert_eq!(kill.sent_obstacle_block_count, sent);
+
+    //two lines from the top, the first line of its own falls this turn and the 18 sent make
+    //the second one
+    let board = crate::test_fixtures::two_lines_from_top();
+    let enemy = GameStatus::default().with_board(board).with_obstacle_block_count(12);
+    let mut packs = vec![Pack::new(&[1, 1, 0, 0]).unique_rotations(); 10];
+    packs[0] = Pack::new(&[0, 7, 5, 6]).unique_rotations();
+    let plans = search.search(&mut simulator, &mut evaluate_cache, &packs, 0, &player, &enemy);
+    let kill = CounterSearch::choose(&plans).unwrap();
+    assert_eq!((kill.action, kill.chain_count), (CounterAction::CounterKill, 9));
+    //the enemy's own obstacles aren't counted as sent
+    assert_eq!(kill.sent_obstacle_block_count, sent);
+    //with 2 of its own, a 7 next to the 3 offsets 1 and only one line falls
+    let enemy = enemy.with_obstacle_block_count(2);
+    let plans = search.search(&mut simulator, &mut evaluate_cache, &packs, 0, &player, &enemy);
+    assert!(plans.iter().all(|plan| plan.action != CounterAction::CounterKill));
+}
@@ -5,10 +5,14 @@ use crate::pack::Pack;
 use crate::search_state::SearchState;
 use crate::simulator;
 use crate::simulator::{Simulator, DIRECTION_YXS};
+use crate::replacement_table::{CacheStats, ReplacementTable};
+use crate::solver_config::DEFAULT_EVALUATE_CACHE_BYTES;
 use crate::solver_config::DEFAULT_FATAL_FIRE_MAX_CHAIN_COUNT;
 
+use crate::zobrist_hash_table;
 use crate::zobrist_hash_table::ZobristHash;
-use fnv::{FnvHashMap, FnvHashSet};
+use fnv::FnvHashSet;
+use std::io::{Read, Write};
 //max 20
 
 //(10 / 13) ^ 0 (10 / 13) ^ 1 (10 / 13) ^ 2
@@ -44,37 +48,160 @@ pub const GAME_SCORE_DEPTH_RATES: [f64; 20] = [
 const NOT_SPAWN_MAX_CHAIN_COUNT: u8 = 7;
 
 
+//"TGEC", the version and the zobrist table fingerprint, then both tables as a count and entries
+const CACHE_FILE_MAGIC: [u8; 4] = *b"TGEC";
+//bump when estimations change their results
+pub const CACHE_FILE_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum CacheFileError {
+    Io(std::io::Error),
+    NotCacheFile,
+    Version(u32),
+    Fingerprint(u64),
+}
+
+impl std::fmt::Display for CacheFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CacheFileError::Io(e) => write!(f, "io error: {}", e),
+            CacheFileError::NotCacheFile => write!(f, "not an evaluation cache file"),
+            CacheFileError::Version(version) => {
+                write!(f, "version {} isn't {}", version, CACHE_FILE_VERSION)
+            }
+            CacheFileError::Fingerprint(fingerprint) => {
+                write!(f, "saved with another zobrist table ({:016x})", fingerprint)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CacheFileError {}
+
+impl From<std::io::Error> for CacheFileError {
+    fn from(e: std::io::Error) -> CacheFileError {
+        CacheFileError::Io(e)
+    }
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64, std::io::Error> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_entry<R: Read>(reader: &mut R) -> Result<(ZobristHash, u8, u8), std::io::Error> {
+    let mut bytes = [0; 10];
+    reader.read_exact(&mut bytes)?;
+    let mut key = [0; 8];
+    key.copy_from_slice(&bytes[..8]);
+    Ok((u64::from_le_bytes(key), bytes[8], bytes[9]))
+}
+
+fn write_entry<W: Write>(writer: &mut W, key: ZobristHash, a: u8, b: u8) -> Result<(), std::io::Error> {
+    writer.write_all(&key.to_le_bytes())?;
+    writer.write_all(&[a, b])
+}
+
 pub struct EvaluateCache {
-    cache_estimate_max_chain_count: FnvHashMap<ZobristHash, (u8, u8)>,
-    cache_estimate_with_erasing_all_max_chain_count: FnvHashMap<ZobristHash, (u8, usize)>,
+    cache_estimate_max_chain_count: ReplacementTable<(u8, u8)>,
+    cache_estimate_with_erasing_all_max_chain_count: ReplacementTable<(u8, usize)>,
 }
 
 impl EvaluateCache {
     pub fn new() -> EvaluateCache {
-        EvaluateCache {
-            cache_estimate_max_chain_count: FnvHashMap::default(),
-            cache_estimate_with_erasing_all_max_chain_count: FnvHashMap::default(),
-        }
+        EvaluateCache::with_capacity_bytes(DEFAULT_EVALUATE_CACHE_BYTES)
     }
     pub fn default() -> EvaluateCache {
+        EvaluateCache::with_capacity_bytes(DEFAULT_EVALUATE_CACHE_BYTES)
+    }
+    //bytes are split between both tables
+    pub fn with_capacity_bytes(bytes: usize) -> EvaluateCache {
         EvaluateCache {
-            cache_estimate_max_chain_count: FnvHashMap::default(),
-            cache_estimate_with_erasing_all_max_chain_count: FnvHashMap::default(),
+            cache_estimate_max_chain_count: ReplacementTable::with_capacity_bytes(bytes / 2),
+            cache_estimate_with_erasing_all_max_chain_count: ReplacementTable::with_capacity_bytes(bytes / 2),
         }
     }
-    /*pub fn empty(&self) -> bool {
-        self.len() == 0
-    }*/
+    pub fn capacity_bytes(&self) -> usize {
+        self.cache_estimate_max_chain_count.capacity_bytes()
+            + self.cache_estimate_with_erasing_all_max_chain_count.capacity_bytes()
+    }
     pub fn len_estimate_max_chain_count(&self) -> usize {
         self.cache_estimate_max_chain_count.len()
     }
     pub fn len_estimate_with_erasing_all_max_chain_count(&self) -> usize {
         self.cache_estimate_with_erasing_all_max_chain_count.len()
     }
+    pub fn stats_estimate_max_chain_count(&self) -> CacheStats {
+        self.cache_estimate_max_chain_count.stats()
+    }
+    pub fn stats_estimate_with_erasing_all_max_chain_count(&self) -> CacheStats {
+        self.cache_estimate_with_erasing_all_max_chain_count.stats()
+    }
+    pub fn stats(&self) -> CacheStats {
+        self.stats_estimate_max_chain_count() + self.stats_estimate_with_erasing_all_max_chain_count()
+    }
+    //entries stay valid for any board, the older ones are just replaced first
+    pub fn new_generation(&mut self) {
+        self.cache_estimate_max_chain_count.new_generation();
+        self.cache_estimate_with_erasing_all_max_chain_count.new_generation();
+    }
     pub fn clear(&mut self) {
         self.cache_estimate_with_erasing_all_max_chain_count.clear();
         self.cache_estimate_max_chain_count.clear();
     }
+    pub fn save<W: Write>(&self, writer: &mut W) -> Result<(), std::io::Error> {
+        writer.write_all(&CACHE_FILE_MAGIC)?;
+        writer.write_all(&CACHE_FILE_VERSION.to_le_bytes())?;
+        writer.write_all(&zobrist_hash_table::board_table_fingerprint().to_le_bytes())?;
+        let table = &self.cache_estimate_max_chain_count;
+        writer.write_all(&(table.len() as u64).to_le_bytes())?;
+        for (key, (chain_count, count)) in table.iter() {
+            write_entry(writer, key, chain_count, count)?;
+        }
+        let table = &self.cache_estimate_with_erasing_all_max_chain_count;
+        writer.write_all(&(table.len() as u64).to_le_bytes())?;
+        for (key, (chain_count, height)) in table.iter() {
+            //a height is below FIELD_HEIGHT
+            write_entry(writer, key, chain_count, height as u8)?;
+        }
+        Ok(())
+    }
+    //adds the saved entries, returns how many were read
+    pub fn load<R: Read>(&mut self, reader: &mut R) -> Result<usize, CacheFileError> {
+        let mut header = [0; 8];
+        reader.read_exact(&mut header)?;
+        if header[..4] != CACHE_FILE_MAGIC {
+            return Err(CacheFileError::NotCacheFile);
+        }
+        let mut version = [0; 4];
+        version.copy_from_slice(&header[4..]);
+        let version = u32::from_le_bytes(version);
+        if version != CACHE_FILE_VERSION {
+            return Err(CacheFileError::Version(version));
+        }
+        let fingerprint = read_u64(reader)?;
+        if fingerprint != zobrist_hash_table::board_table_fingerprint() {
+            return Err(CacheFileError::Fingerprint(fingerprint));
+        }
+        //read everything first, a broken file adds nothing
+        let mut max_chain_counts = Vec::new();
+        for _ in 0..read_u64(reader)? {
+            max_chain_counts.push(read_entry(reader)?);
+        }
+        let mut erasing_all_max_chain_counts = Vec::new();
+        for _ in 0..read_u64(reader)? {
+            erasing_all_max_chain_counts.push(read_entry(reader)?);
+        }
+        for &(key, chain_count, count) in max_chain_counts.iter() {
+            self.cache_estimate_max_chain_count.insert(key, (chain_count, count));
+        }
+        for &(key, chain_count, height) in erasing_all_max_chain_counts.iter() {
+            self.cache_estimate_with_erasing_all_max_chain_count
+                .insert(key, (chain_count, height as usize));
+        }
+        Ok(max_chain_counts.len() + erasing_all_max_chain_counts.len())
+    }
     fn is_erasable(y: usize, x: usize, board: &Board) -> bool {
         //top
         if y + 1 < FIELD_HEIGHT {
@@ -123,9 +250,9 @@ impl EvaluateCache {
     ) -> (u8, usize) {
         if let Some(chain_count) = self
             .cache_estimate_with_erasing_all_max_chain_count
-            .get(&board.zobrist_hash())
+            .get(board.zobrist_hash())
         {
-            return chain_count.clone();
+            return chain_count;
         }
         let mut height = 0;
         let mut max_chain_count = 0;
@@ -172,9 +299,9 @@ impl EvaluateCache {
     ) -> (u8, u8) {
         if let Some(cache_max_chain_count) = self
             .cache_estimate_max_chain_count
-            .get(&board.zobrist_hash())
+            .get(board.zobrist_hash())
         {
-            return *cache_max_chain_count;
+            return cache_max_chain_count;
         }
         let mut estimated_max_chain: (u8, u8) = (0, 0);
 
@@ -254,6 +381,8 @@ impl EvaluateCache {
         let mut search_score: f64 = 0.0;
 
         let mut board = search_state.board();
+        //the erasing estimate sees the board before the obstacles fall
+        let estimated_max_erasing_chain_count = self.estimate_with_erasing_all_max_chain_count(simulator, &board);
         if search_state.obstacle_block_count() >= 10 {
             board.drop_obstacles();
 
@@ -264,8 +393,6 @@ impl EvaluateCache {
             let (estimated_max_chain_count, _) = self.estimate_max_chain_count(simulator, &board);
             search_score += estimated_max_chain_count as f64 * 10e5;
         } else {*/
-        let estimated_max_erasing_chain_count =
-            self.estimate_with_erasing_all_max_chain_count(simulator, &search_state.board());
         search_score += estimated_max_erasing_chain_count.0 as f64 * 10e5;
 
         // count live block
@@ -731,3 +858,44 @@ fn test_estimate_max_chain_count() {
         evaluate_cache.estimate_max_chain_count(&mut Simulator::new(), &Board::new(board));
     debug_assert_eq!((max_chain_count, height), (11, 1));
 }
+
+#[test]
+fn test_evaluate_cache_file() {
+    let mut rnd = crate::xorshift::Xorshift::with_seed(5);
+    let boards: Vec<Board> = (0..20)
+        .map(|_| crate::reference_simulator::to_board(&crate::reference_simulator::random_stable_grid(&mut rnd, 12)))
+        .collect();
+    let mut simulator = Simulator::new();
+    let mut evaluate_cache = EvaluateCache::with_capacity_bytes(1 << 20);
+    let expected: Vec<((u8, u8), (u8, usize))> = boards
+        .iter()
+        .map(|board| {
+            (
+                evaluate_cache.estimate_max_chain_count(&mut simulator, board),
+                evaluate_cache.estimate_with_erasing_all_max_chain_count(&mut simulator, board),
+            )
+        })
+        .collect();
+    let mut bytes = Vec::new();
+    evaluate_cache.save(&mut bytes).unwrap();
+
+    //a warm start answers from the file
+    let mut loaded = EvaluateCache::with_capacity_bytes(1 << 20);
+    assert_eq!(loaded.load(&mut bytes.as_slice()).unwrap(), evaluate_cache.stats().len);
+    for (board, &(max_chain_count, erasing_all)) in boards.iter().zip(expected.iter()) {
+        assert_eq!(loaded.estimate_max_chain_count(&mut simulator, board), max_chain_count);
+        assert_eq!(loaded.estimate_with_erasing_all_max_chain_count(&mut simulator, board), erasing_all);
+    }
+    assert_eq!(loaded.stats().misses, 0);
+
+    let mut stale = bytes.clone();
+    stale[4] += 1;
+    assert!(matches!(EvaluateCache::with_capacity_bytes(1 << 20).load(&mut stale.as_slice()), Err(CacheFileError::Version(2))));
+    let mut stale = bytes.clone();
+    stale[8] ^= 1;
+    assert!(matches!(EvaluateCache::with_capacity_bytes(1 << 20).load(&mut stale.as_slice()), Err(CacheFileError::Fingerprint(_))));
+    assert!(matches!(EvaluateCache::with_capacity_bytes(1 << 20).load(&mut &b"not a cache"[..]), Err(CacheFileError::NotCacheFile)));
+    let mut truncated = EvaluateCache::with_capacity_bytes(1 << 20);
+    assert!(matches!(truncated.load(&mut &bytes[..bytes.len() - 1]), Err(CacheFileError::Io(_))));
+    assert_eq!(truncated.stats().len, 0);
+}
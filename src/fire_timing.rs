@@ -0,0 +1,348 @@
+//Simultaneous-move UCT over when to fire. Every node of the tree is a decision turn where both
+//players pick an action at the same time by UCB1 on their own statistics (decoupled UCT), a joint
+//action leads to the next decision turn. Below the tree the game goes on with a fast greedy
+//rollout for both on the real packs.
+use crate::board::{Board, FIELD_WIDTH};
+use crate::command::Command;
+use crate::evaluation::EvaluateCache;
+use crate::game_status::GameStatus;
+use crate::pack::Pack;
+use crate::referee::{GameResult, Referee, SPELL_SKILL_POINT};
+use crate::simulator;
+use crate::simulator::{Child, Simulator, SPELL_BLOCK};
+use crate::xorshift::Xorshift;
+
+//fire actions for the largest chain counts only
+const MAX_FIRE_ACTION_COUNT: usize = 3;
+//a rollout player fires at this chain count or when its board gets high
+const ROLLOUT_FIRE_CHAIN_COUNT: u8 = 10;
+const ROLLOUT_FIRE_HEIGHT: usize = 12;
+//obstacles worth about 73% at the end of a rollout
+const LEAF_OBSTACLE_SCALE: f64 = 20.0;
+const ROLLOUT_CACHE_BYTES: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FireAction {
+    //the drop and its chain count
+    Fire(Command, u8),
+    //the best drop which doesn't erase anything
+    Build(Command),
+    Spell,
+}
+
+impl FireAction {
+    pub fn command(&self) -> Command {
+        match *self {
+            FireAction::Fire(command, _) | FireAction::Build(command) => command,
+            FireAction::Spell => Command::Spell,
+        }
+    }
+}
+
+impl std::fmt::Display for FireAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FireAction::Fire(command, chain_count) => write!(f, "fire {} chain {}", command, chain_count),
+            FireAction::Build(command) => write!(f, "build {}", command),
+            FireAction::Spell => write!(f, "spell"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ActionStats {
+    pub action: FireAction,
+    pub visits: u32,
+    //a win counts 1 and a draw 0.5, from the view of the player taking the action
+    pub wins: f64,
+}
+
+impl ActionStats {
+    pub fn win_probability(&self) -> f64 {
+        if self.visits == 0 {
+            return 0.0;
+        }
+        self.wins / self.visits as f64
+    }
+}
+
+//the statistics of the root
+#[derive(Debug, Clone)]
+pub struct FireTimingResult {
+    pub player: Vec<ActionStats>,
+    pub enemy: Vec<ActionStats>,
+    //decision turns in the tree, the root included
+    pub node_count: usize,
+}
+
+impl FireTimingResult {
+    pub fn best(&self) -> Option<&ActionStats> {
+        self.player.iter().max_by(|a, b| a.win_probability().partial_cmp(&b.win_probability()).unwrap())
+    }
+    pub fn action(&self, action: FireAction) -> Option<&ActionStats> {
+        self.player.iter().find(|stats| stats.action == action)
+    }
+}
+
+//a decision turn, the actions are fixed when the node is made
+struct Node {
+    referee: Referee,
+    player: Vec<ActionStats>,
+    enemy: Vec<ActionStats>,
+    visits: u32,
+    //the player's and the enemy's action indices and the node they lead to
+    children: Vec<(usize, usize, usize)>,
+}
+
+pub struct FireTiming {
+    iterations: usize,
+    tree_depth: usize,
+    rollout_turns: usize,
+    exploration: f64,
+    rnd: Xorshift,
+    simulator: Simulator,
+    evaluate_cache: EvaluateCache,
+    children: Vec<Child>,
+    nodes: Vec<Node>,
+}
+
+impl FireTiming {
+    pub fn default() -> FireTiming {
+        FireTiming {
+            iterations: 300,
+            tree_depth: 3,
+            rollout_turns: 12,
+            exploration: std::f64::consts::SQRT_2,
+            rnd: Xorshift::with_seed(1024),
+            simulator: Simulator::new(),
+            evaluate_cache: EvaluateCache::with_capacity_bytes(ROLLOUT_CACHE_BYTES),
+            children: Vec::new(),
+            nodes: Vec::new(),
+        }
+    }
+    pub fn with_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+    //decision turns in the tree, 1 is a flat bandit at the root
+    pub fn with_tree_depth(mut self, tree_depth: usize) -> Self {
+        self.tree_depth = tree_depth;
+        self
+    }
+    pub fn with_rollout_turns(mut self, rollout_turns: usize) -> Self {
+        self.rollout_turns = rollout_turns;
+        self
+    }
+    pub fn with_exploration(mut self, exploration: f64) -> Self {
+        self.exploration = exploration;
+        self
+    }
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rnd = Xorshift::with_seed(seed);
+        self
+    }
+    //packs are the raw packs of the whole game, turn is the current turn
+    pub fn search(&mut self, packs: &[Pack], turn: usize, player: &GameStatus, enemy: &GameStatus) -> FireTimingResult {
+        let root = Referee::new(packs.to_vec()).with_turn(turn).with_players(player.clone(), enemy.clone());
+        self.nodes.clear();
+        let root = self.node(root);
+        let searchable = !root.player.is_empty() && !root.enemy.is_empty();
+        self.nodes.push(root);
+        let mut path = Vec::with_capacity(self.tree_depth);
+        for _ in 0..self.iterations {
+            if !searchable {
+                break;
+            }
+            path.clear();
+            let mut idx = 0;
+            let value = loop {
+                let node = &self.nodes[idx];
+                let player_idx = select(&node.player, node.visits, self.exploration);
+                let enemy_idx = select(&node.enemy, node.visits, self.exploration);
+                path.push((idx, player_idx, enemy_idx));
+                if let Some(&(_, _, child)) = node.children.iter().find(|&&(p, e, _)| (p, e) == (player_idx, enemy_idx)) {
+                    idx = child;
+                    continue;
+                }
+                let mut referee = node.referee.clone();
+                let commands = [node.player[player_idx].action.command(), node.enemy[enemy_idx].action.command()];
+                referee.step(&mut self.simulator, commands);
+                //a finished game or a player without a living drop is left to the rollout
+                if path.len() < self.tree_depth && referee.result().is_none() {
+                    let child = self.node(referee.clone());
+                    if !child.player.is_empty() && !child.enemy.is_empty() {
+                        self.nodes.push(child);
+                        let child = self.nodes.len() - 1;
+                        self.nodes[idx].children.push((player_idx, enemy_idx, child));
+                    }
+                }
+                break self.rollout(&mut referee);
+            };
+            for &(idx, player_idx, enemy_idx) in path.iter() {
+                let node = &mut self.nodes[idx];
+                node.visits += 1;
+                node.player[player_idx].visits += 1;
+                node.player[player_idx].wins += value;
+                node.enemy[enemy_idx].visits += 1;
+                node.enemy[enemy_idx].wins += 1.0 - value;
+            }
+        }
+        FireTimingResult {
+            player: self.nodes[0].player.clone(),
+            enemy: self.nodes[0].enemy.clone(),
+            node_count: self.nodes.len(),
+        }
+    }
+    fn node(&mut self, referee: Referee) -> Node {
+        let stats = |action| ActionStats { action, visits: 0, wins: 0.0 };
+        Node {
+            player: self.actions(&referee, 0).into_iter().map(stats).collect(),
+            enemy: self.actions(&referee, 1).into_iter().map(stats).collect(),
+            referee,
+            visits: 0,
+            children: Vec::new(),
+        }
+    }
+    //the board a player drops its pack on, after its pending obstacles fell
+    fn board_before_drop(status: &GameStatus) -> Board {
+        let mut board = status.board();
+        if status.obstacle_block_count() >= FIELD_WIDTH as u32 {
+            board.drop_obstacles();
+        }
+        board
+    }
+    fn expand(&mut self, referee: &Referee, idx: usize) -> Board {
+        let board = FireTiming::board_before_drop(referee.player(idx));
+        let pack = referee.pack();
+        self.children.clear();
+        let orientations = pack.unique_rotations();
+        self.children.extend(self.simulator.expand(&board, &orientations));
+        self.children.retain(|child| !child.board.is_game_over());
+        board
+    }
+    fn actions(&mut self, referee: &Referee, idx: usize) -> Vec<FireAction> {
+        self.expand(referee, idx);
+        let mut fires: Vec<(u8, Command)> = Vec::new();
+        for child in self.children.iter().filter(|child| child.chain_count > 0) {
+            if !fires.iter().any(|&(chain_count, _)| chain_count == child.chain_count) {
+                fires.push((child.chain_count, Command::Drop((child.point, child.rotate_count))));
+            }
+        }
+        fires.sort_by_key(|&(chain_count, _)| std::cmp::Reverse(chain_count));
+        let mut actions: Vec<FireAction> = fires
+            .iter()
+            .take(MAX_FIRE_ACTION_COUNT)
+            .map(|&(chain_count, command)| FireAction::Fire(command, chain_count))
+            .collect();
+        if let Some(command) = self.build_command(false) {
+            actions.push(FireAction::Build(command));
+        }
+        if referee.player(idx).skill_point() >= SPELL_SKILL_POINT {
+            actions.push(FireAction::Spell);
+        }
+        actions
+    }
+    //the non-erasing drop with the largest chain left to fire, randomized a little in rollouts
+    fn build_command(&mut self, noise: bool) -> Option<Command> {
+        let mut best: Option<(f64, Command)> = None;
+        for i in 0..self.children.len() {
+            let child = self.children[i];
+            if child.chain_count > 0 {
+                continue;
+            }
+            let (chain_count, _) = self.evaluate_cache.estimate_max_chain_count(&mut self.simulator, &child.board);
+            let max_height = child.board.heights.iter().max().cloned().unwrap_or(0);
+            let mut score = chain_count as f64 * 10.0 - max_height as f64;
+            if noise {
+                score += self.rnd.randf() * 5.0;
+            }
+            if best.is_none_or(|(best_score, _)| score > best_score) {
+                best = Some((score, Command::Drop((child.point, child.rotate_count))));
+            }
+        }
+        best.map(|(_, command)| command)
+    }
+    fn rollout_command(&mut self, referee: &Referee, idx: usize) -> Command {
+        let status = referee.player(idx);
+        let board = self.expand(referee, idx);
+        if self.children.is_empty() {
+            //every drop loses
+            return Command::Drop((0, 0));
+        }
+        let fire = self.children.iter().max_by_key(|child| child.chain_count).cloned().unwrap();
+        let max_height = board.heights.iter().max().cloned().unwrap_or(0);
+        if fire.chain_count >= ROLLOUT_FIRE_CHAIN_COUNT || (fire.chain_count > 0 && max_height >= ROLLOUT_FIRE_HEIGHT) {
+            return Command::Drop((fire.point, fire.rotate_count));
+        }
+        if status.skill_point() >= SPELL_SKILL_POINT && (0..FIELD_WIDTH).any(|x| (0..board.heights[x]).any(|y| board.get(y, x) == SPELL_BLOCK)) {
+            return Command::Spell;
+        }
+        match self.build_command(true) {
+            Some(command) => command,
+            None => Command::Drop((fire.point, fire.rotate_count)),
+        }
+    }
+    //the value of the position for the player, 1 is a win
+    fn rollout(&mut self, referee: &mut Referee) -> f64 {
+        for _ in 0..self.rollout_turns {
+            if referee.result().is_some() {
+                break;
+            }
+            let commands = [self.rollout_command(referee, 0), self.rollout_command(referee, 1)];
+            referee.step(&mut self.simulator, commands);
+        }
+        match referee.result() {
+            Some(GameResult::Win(0)) => 1.0,
+            Some(GameResult::Win(_)) => 0.0,
+            Some(GameResult::Draw) => 0.5,
+            None => self.evaluate_leaf(referee),
+        }
+    }
+    //obstacles each player can still send minus what it has to take
+    fn evaluate_leaf(&mut self, referee: &Referee) -> f64 {
+        let mut threats = [0.0; 2];
+        for (idx, threat) in threats.iter_mut().enumerate() {
+            let status = referee.player(idx);
+            let (chain_count, _) = self.evaluate_cache.estimate_max_chain_count(&mut self.simulator, &status.board());
+            *threat = simulator::calculate_obstacle_count_from_chain_count(chain_count) as f64
+                - status.obstacle_block_count() as f64;
+        }
+        1.0 / (1.0 + f64::exp(-(threats[0] - threats[1]) / LEAF_OBSTACLE_SCALE))
+    }
+}
+
+//UCB1 over the visits of a node, every action is tried once first
+fn select(stats: &[ActionStats], visits: u32, exploration: f64) -> usize {
+    if let Some(idx) = stats.iter().position(|stats| stats.visits == 0) {
+        return idx;
+    }
+    let log_total = ((visits + 1) as f64).ln();
+    let ucb = |stats: &ActionStats| stats.win_probability() + exploration * (log_total / stats.visits as f64).sqrt();
+    (0..stats.len()).max_by(|&a, &b| ucb(&stats[a]).partial_cmp(&ucb(&stats[b])).unwrap()).unwrap()
+}
+
+#[test]
+fn test_fire_timing() {
+    //7 next to 3 makes 2 chains and sends the obstacle which drops a line on the enemy
+    let player = crate::test_fixtures::seven_next_to_three();
+    let enemy = crate::test_fixtures::wall();
+    let packs = vec![Pack::new(&[7, 0, 0, 0]); crate::solver::MAX_TURN];
+    let player = GameStatus::default().with_board(player);
+    let enemy = GameStatus::default().with_board(enemy).with_obstacle_block_count(9);
+    let mut fire_timing = FireTiming::default().with_iterations(40).with_rollout_turns(4);
+    let result = fire_timing.search(&packs, 0, &player, &enemy);
+    assert_eq!(result.player.iter().map(|stats| stats.visits).sum::<u32>(), 40);
+    assert!(result.player.iter().all(|stats| stats.action != FireAction::Spell));
+    assert!(result.player.iter().any(|stats| matches!(stats.action, FireAction::Build(_))));
+    let fire = result.player.iter().find(|stats| matches!(stats.action, FireAction::Fire(_, 2))).unwrap();
+    assert_eq!(fire.win_probability(), 1.0);
+    assert_eq!(result.best().unwrap().action, fire.action);
+    assert!(result.player.iter().all(|stats| (0.0..=1.0).contains(&stats.win_probability())));
+    assert!(result.node_count > 1);
+
+    //a tree of one decision turn is a flat bandit at the root
+    let mut fire_timing = FireTiming::default().with_iterations(40).with_rollout_turns(4).with_tree_depth(1);
+    let flat = fire_timing.search(&packs, 0, &player, &enemy);
+    assert_eq!(flat.node_count, 1);
+    assert_eq!(flat.best().unwrap().action, fire.action);
+}
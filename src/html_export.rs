@@ -0,0 +1,300 @@
+use crate::board::{block_char, Board, FIELD_HEIGHT, FIELD_WIDTH, INPUT_FIELD_HEIGHT};
+use crate::command::Command;
+use crate::match_record::MatchRecord;
+use crate::search_result::format_principal_variation;
+use crate::referee::Referee;
+use crate::simulator::Simulator;
+use crate::viewer::{record_frames, Frame, Side};
+
+//per turn summary shown next to the boards
+#[derive(Debug, Clone)]
+pub struct TurnAnnotation {
+    pub turn: usize,
+    pub player_chain_count: u8,
+    pub enemy_chain_count: u8,
+    pub player_obstacles_sent: u32,
+    pub enemy_obstacles_sent: u32,
+    pub principal_variation: Option<Vec<Command>>,
+}
+
+//obstacles each side sent after offsetting its own, replayed by the referee; an unknown command
+//sends nothing
+fn obstacles_sent(record: &MatchRecord, index: usize, simulator: &mut Simulator) -> (u32, u32) {
+    let turn_record = &record.turns[index];
+    let enemy_command = record.infer_enemy_command(index, simulator);
+    if turn_record.command.is_none() && enemy_command.is_none() {
+        return (0, 0);
+    }
+    //the other side's command doesn't change what a side sends
+    let placeholder = Command::Drop((0, 0));
+    let mut referee = Referee::new(record.packs.clone())
+        .with_turn(turn_record.turn)
+        .with_players(turn_record.player.clone(), turn_record.enemy.clone());
+    let outcomes = referee.step(
+        simulator,
+        [turn_record.command.unwrap_or(placeholder), enemy_command.unwrap_or(placeholder)],
+    );
+    (
+        turn_record.command.map_or(0, |_| outcomes[0].sent_obstacle_block_count),
+        enemy_command.map_or(0, |_| outcomes[1].sent_obstacle_block_count),
+    )
+}
+
+pub fn annotate(record: &MatchRecord, frames: &[Frame]) -> Vec<TurnAnnotation> {
+    let mut simulator = Simulator::new();
+    record
+        .turns
+        .iter()
+        .enumerate()
+        .map(|(index, turn_record)| {
+            let turn_frames = frames.iter().filter(|frame| frame.turn == turn_record.turn);
+            let (player_chain_count, enemy_chain_count) =
+                turn_frames.fold((0, 0), |(player, enemy), frame| {
+                    let enemy_chain_count = frame.enemy.as_ref().map_or(0, |side| side.chain_count);
+                    (
+                        std::cmp::max(player, frame.player.chain_count),
+                        std::cmp::max(enemy, enemy_chain_count),
+                    )
+                });
+            let (player_obstacles_sent, enemy_obstacles_sent) = obstacles_sent(record, index, &mut simulator);
+            TurnAnnotation {
+                turn: turn_record.turn,
+                player_chain_count,
+                enemy_chain_count,
+                player_obstacles_sent,
+                enemy_obstacles_sent,
+                principal_variation: turn_record.principal_variation(),
+            }
+        })
+        .collect()
+}
+
+//all rows top-down including the hidden rows
+fn encode_board(board: &Board) -> String {
+    let mut s = String::with_capacity(FIELD_HEIGHT * FIELD_WIDTH);
+    for y in (0..FIELD_HEIGHT).rev() {
+        for x in 0..FIELD_WIDTH {
+            s.push(block_char(board.get(y, x)));
+        }
+    }
+    s
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '<' => escaped.push_str("\\u003c"),
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn side_json(side: &Side) -> String {
+    let command = side.command.map(|c| c.to_string()).unwrap_or_else(|| "?".to_string());
+    format!(
+        "{{\"label\":{},\"board\":{},\"score\":{},\"skill\":{},\"obstacle\":{},\"command\":{}}}",
+        json_string(&side.label),
+        json_string(&encode_board(&side.board)),
+        side.cumulative_game_score,
+        side.skill_point,
+        side.obstacle_block_count,
+        json_string(&command)
+    )
+}
+
+fn frame_json(frame: &Frame) -> String {
+    let enemy = frame.enemy.as_ref().map_or("null".to_string(), side_json);
+    format!(
+        "{{\"turn\":{},\"player\":{},\"enemy\":{}}}",
+        frame.turn,
+        side_json(&frame.player),
+        enemy
+    )
+}
+
+fn annotation_json(annotation: &TurnAnnotation) -> String {
+    let pv = annotation
+        .principal_variation
+        .as_ref()
+        .map_or("null".to_string(), |pv| json_string(&format_principal_variation(pv)));
+    format!(
+        "{{\"turn\":{},\"chain\":[{},{}],\"sent\":[{},{}],\"pv\":{}}}",
+        annotation.turn,
+        annotation.player_chain_count,
+        annotation.enemy_chain_count,
+        annotation.player_obstacles_sent,
+        annotation.enemy_obstacles_sent,
+        pv
+    )
+}
+
+pub fn export_html(record: &MatchRecord, title: &str) -> String {
+    let frames = record_frames(record);
+    let annotations = annotate(record, &frames);
+    let frames_json: Vec<String> = frames.iter().map(frame_json).collect();
+    let annotations_json: Vec<String> = annotations.iter().map(annotation_json).collect();
+    HTML_TEMPLATE
+        .replace("{{TITLE}}", &title.replace('<', "&lt;"))
+        .replace("{{WIDTH}}", &FIELD_WIDTH.to_string())
+        .replace("{{HEIGHT}}", &FIELD_HEIGHT.to_string())
+        .replace("{{HIDDEN}}", &(FIELD_HEIGHT - INPUT_FIELD_HEIGHT).to_string())
+        .replace("{{FRAMES}}", &format!("[{}]", frames_json.join(",\n")))
+        .replace("{{TURNS}}", &format!("[{}]", annotations_json.join(",\n")))
+}
+
+const HTML_TEMPLATE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{{TITLE}}</title>
+<style>
+body { font-family: monospace; background: #222; color: #eee; }
+.boards { display: flex; gap: 32px; }
+.side h2 { margin: 4px 0; font-size: 16px; }
+table { border-collapse: collapse; margin-top: 8px; }
+td { padding: 2px 8px; border: 1px solid #555; }
+input[type=range] { width: 600px; }
+</style>
+</head>
+<body>
+<h1>{{TITLE}}</h1>
+<div>
+turn <input type="range" id="turn" min="0" value="0"> <span id="turn-label"></span>
+</div>
+<div>
+<button id="prev">&lt; stage</button>
+<button id="next">stage &gt;</button>
+<button id="play">play</button>
+<span id="stage-label"></span>
+</div>
+<div class="boards">
+<div class="side"><h2 id="player-title">player</h2><svg id="player"></svg><div id="player-info"></div></div>
+<div class="side"><h2 id="enemy-title">enemy</h2><svg id="enemy"></svg><div id="enemy-info"></div></div>
+</div>
+<table id="annotation"></table>
+<script>
+const WIDTH = {{WIDTH}}, HEIGHT = {{HEIGHT}}, HIDDEN = {{HIDDEN}}, CELL = 20;
+const FRAMES = {{FRAMES}};
+const TURNS = {{TURNS}};
+const COLORS = {".": "#111", "#": "#777", "1": "#e53935", "2": "#43a047", "3": "#fdd835",
+  "4": "#1e88e5", "5": "#8e24aa", "6": "#00acc1", "7": "#5c6bc0", "8": "#ffb300", "9": "#7cb342"};
+let frameIndex = 0;
+let timer = null;
+
+function svgBoard(svg, board) {
+  svg.setAttribute("width", WIDTH * CELL);
+  svg.setAttribute("height", HEIGHT * CELL);
+  let s = "";
+  for (let row = 0; row < HEIGHT; row++) {
+    for (let x = 0; x < WIDTH; x++) {
+      const c = board[row * WIDTH + x];
+      const opacity = row < HIDDEN ? 0.5 : 1.0;
+      s += `<rect x="${x * CELL}" y="${row * CELL}" width="${CELL - 1}" height="${CELL - 1}" fill="${COLORS[c]}" opacity="${opacity}"/>`;
+      if (c >= "1" && c <= "9") {
+        s += `<text x="${x * CELL + CELL / 2}" y="${row * CELL + CELL * 0.75}" font-size="${CELL * 0.7}" text-anchor="middle" fill="#fff">${c}</text>`;
+      }
+    }
+  }
+  s += `<line x1="0" y1="${HIDDEN * CELL - 1}" x2="${WIDTH * CELL}" y2="${HIDDEN * CELL - 1}" stroke="#f44" stroke-width="2"/>`;
+  svg.innerHTML = s;
+}
+
+function info(side) {
+  return `${side.label}<br>score ${side.score} skill ${side.skill} obstacle ${side.obstacle}<br>command ${side.command}`;
+}
+
+function render() {
+  const frame = FRAMES[frameIndex];
+  svgBoard(document.getElementById("player"), frame.player.board);
+  document.getElementById("player-info").innerHTML = info(frame.player);
+  if (frame.enemy) {
+    svgBoard(document.getElementById("enemy"), frame.enemy.board);
+    document.getElementById("enemy-info").innerHTML = info(frame.enemy);
+  }
+  const turnIndex = TURNS.findIndex(t => t.turn === frame.turn);
+  const stages = FRAMES.filter(f => f.turn === frame.turn).length;
+  const stage = frameIndex - FRAMES.findIndex(f => f.turn === frame.turn);
+  document.getElementById("turn").value = turnIndex;
+  document.getElementById("turn-label").textContent = `${frame.turn}`;
+  document.getElementById("stage-label").textContent = `stage ${stage + 1}/${stages}`;
+  const t = TURNS[turnIndex];
+  document.getElementById("annotation").innerHTML =
+    `<tr><td></td><td>player</td><td>enemy</td></tr>` +
+    `<tr><td>score</td><td>${frame.player.score}</td><td>${frame.enemy ? frame.enemy.score : ""}</td></tr>` +
+    `<tr><td>chain</td><td>${t.chain[0]}</td><td>${t.chain[1]}</td></tr>` +
+    `<tr><td>obstacles sent</td><td>${t.sent[0]}</td><td>${t.sent[1]}</td></tr>` +
+    `<tr><td>principal variation</td><td colspan="2">${t.pv === null ? "-" : t.pv}</td></tr>`;
+}
+
+function step(delta) {
+  frameIndex = Math.min(FRAMES.length - 1, Math.max(0, frameIndex + delta));
+  render();
+}
+
+document.getElementById("turn").max = TURNS.length - 1;
+document.getElementById("turn").addEventListener("input", e => {
+  const turn = TURNS[Number(e.target.value)].turn;
+  frameIndex = FRAMES.findIndex(f => f.turn === turn);
+  render();
+});
+document.getElementById("prev").addEventListener("click", () => step(-1));
+document.getElementById("next").addEventListener("click", () => step(1));
+document.getElementById("play").addEventListener("click", () => {
+  if (timer) {
+    clearInterval(timer);
+    timer = null;
+  } else {
+    timer = setInterval(() => step(1), 300);
+  }
+});
+document.addEventListener("keydown", e => {
+  if (e.key === "ArrowRight") step(1);
+  if (e.key === "ArrowLeft") step(-1);
+});
+if (FRAMES.length > 0) render();
+</script>
+</body>
+</html>
+"##;
+
+#[test]
+fn test_export_html() {
+    let record = MatchRecord::parse(&crate::match_record::sample_record_text()).unwrap();
+    let frames = record_frames(&record);
+    let annotations = annotate(&record, &frames);
+    assert_eq!(annotations.len(), 2);
+    assert_eq!(annotations[0].player_chain_count, 0);
+    assert_eq!(
+        annotations[0].principal_variation,
+        Some(vec![Command::Drop((8, 0)), Command::Drop((0, 1))])
+    );
+    assert_eq!(annotations[1].principal_variation, None);
+
+    let html = export_html(&record, "sample <match>");
+    assert!(html.contains("<title>sample &lt;match></title>"));
+    assert!(html.contains("\"pv\":\"8 0, 0 1\""));
+    assert!(!html.contains("{{"));
+    //two frames per turn
+    assert_eq!(html.matches("\"turn\":0,\"player\"").count(), 2);
+    let board = encode_board(&record.turns[1].player.board());
+    assert_eq!(board.len(), FIELD_HEIGHT * FIELD_WIDTH);
+    assert!(board.ends_with("........94"));
+    assert_eq!(json_string("a\"b<"), "\"a\\\"b\\u003c\"");
+
+    //a chain of 2 sends 1 obstacle, unless it offsets a pending one
+    let mut record = record;
+    let board = crate::test_fixtures::seven_next_to_three();
+    record.packs[0] = crate::pack::Pack::new(&[7, 0, 0, 0]);
+    let child = Simulator::new().expand(&board, &record.packs[0].unique_rotations()).find(|child| child.chain_count == 2).unwrap();
+    record.turns[0].command = Some(Command::Drop((child.point, child.rotate_count)));
+    record.turns[0].player = record.turns[0].player.clone().with_board(board);
+    assert_eq!(obstacles_sent(&record, 0, &mut Simulator::new()).0, 1);
+    record.turns[0].player = record.turns[0].player.clone().with_obstacle_block_count(5);
+    assert_eq!(obstacles_sent(&record, 0, &mut Simulator::new()).0, 0);
+}
@@ -0,0 +1,228 @@
+//Whether an attack kills the enemy. The obstacles land on the enemy's real board a line per turn
+//like in the referee, while the enemy tries every drop and spell on the known packs to offset
+//them with its own chains or to outlast them.
+use crate::board::{Board, FIELD_WIDTH};
+use crate::command::Command;
+use crate::game_status::GameStatus;
+use crate::pack::Pack;
+use crate::referee::{calculate_skill_reduce, CHAIN_SKILL_POINT, MAX_SKILL_POINT, SPELL_SKILL_POINT};
+use crate::simulator;
+use crate::simulator::Simulator;
+use crate::zobrist_hash_table::ZobristHash;
+use fnv::FnvHashMap;
+
+pub const DEFAULT_LETHAL_NODE_LIMIT: usize = 20_000;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Lethal {
+    //the first command of a line which leaves the enemy alive with less than a line to come
+    Escape(Command),
+    //the enemy dies whatever it does, in this many turns counting the turn of the attack
+    Dies(usize),
+    //the search ran out of nodes
+    Unknown,
+}
+
+impl Lethal {
+    pub fn is_lethal(&self) -> bool {
+        matches!(self, Lethal::Dies(_))
+    }
+}
+
+impl std::fmt::Display for Lethal {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Lethal::Escape(command) => write!(f, "escapes by {}", command),
+            Lethal::Dies(turns) => write!(f, "dies in {} turns", turns),
+            Lethal::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+//the enemy before its drop
+#[derive(Debug, Copy, Clone)]
+struct EnemyState {
+    board: Board,
+    obstacle_block_count: u32,
+    skill_point: u32,
+}
+
+pub struct LethalCheck {
+    node_limit: usize,
+    node_count: usize,
+    //turns a known dead state lasts
+    dead: FnvHashMap<(usize, ZobristHash, u32, u32), usize>,
+}
+
+impl LethalCheck {
+    pub fn default() -> LethalCheck {
+        LethalCheck { node_limit: DEFAULT_LETHAL_NODE_LIMIT, node_count: 0, dead: FnvHashMap::default() }
+    }
+    pub fn with_node_limit(mut self, node_limit: usize) -> Self {
+        self.node_limit = node_limit;
+        self
+    }
+    //states searched by the last check
+    pub fn node_count(&self) -> usize {
+        self.node_count
+    }
+    //the player fires a chain this turn, its own pending obstacles are offset first
+    pub fn check_chain(
+        &mut self,
+        simulator: &mut Simulator,
+        packs: &[Vec<(Pack, usize)>],
+        turn: usize,
+        player: &GameStatus,
+        enemy: &GameStatus,
+        chain_count: u8,
+    ) -> Lethal {
+        let obstacle_block_count = simulator::calculate_obstacle_count_from_chain_count(chain_count);
+        let skill_reduce = calculate_skill_reduce(chain_count);
+        let sent = obstacle_block_count.saturating_sub(player.obstacle_block_count());
+        self.check(simulator, packs, turn, enemy, sent, skill_reduce)
+    }
+    //the player gains the game score this turn, by a chain or a spell
+    pub fn check_game_score(
+        &mut self,
+        simulator: &mut Simulator,
+        packs: &[Vec<(Pack, usize)>],
+        turn: usize,
+        player: &GameStatus,
+        enemy: &GameStatus,
+        game_score: u32,
+    ) -> Lethal {
+        let sent = simulator::calculate_obstacle_count(game_score, 0).saturating_sub(player.obstacle_block_count());
+        self.check(simulator, packs, turn, enemy, sent, 0)
+    }
+    //sent obstacles reach the enemy after its drop this turn
+    pub fn check(
+        &mut self,
+        simulator: &mut Simulator,
+        packs: &[Vec<(Pack, usize)>],
+        turn: usize,
+        enemy: &GameStatus,
+        sent_obstacle_block_count: u32,
+        skill_reduce: u32,
+    ) -> Lethal {
+        self.node_count = 0;
+        self.dead.clear();
+        let state = EnemyState {
+            board: enemy.board(),
+            obstacle_block_count: enemy.obstacle_block_count(),
+            skill_point: enemy.skill_point(),
+        };
+        self.search(simulator, packs, turn, state, sent_obstacle_block_count, skill_reduce)
+    }
+    fn search(
+        &mut self,
+        simulator: &mut Simulator,
+        packs: &[Vec<(Pack, usize)>],
+        turn: usize,
+        mut state: EnemyState,
+        incoming: u32,
+        skill_reduce: u32,
+    ) -> Lethal {
+        let key = (turn, state.board.zobrist_hash(), state.obstacle_block_count, state.skill_point);
+        if incoming == 0 {
+            if let Some(&turns) = self.dead.get(&key) {
+                return Lethal::Dies(turns);
+            }
+        }
+        self.node_count += 1;
+        if self.node_count > self.node_limit {
+            return Lethal::Unknown;
+        }
+        if state.obstacle_block_count >= FIELD_WIDTH as u32 {
+            state.board.drop_obstacles();
+            state.board.debug_check_invariants();
+            state.obstacle_block_count -= FIELD_WIDTH as u32;
+        }
+        //the outcome of every move, drops with chains first as they offset obstacles
+        let mut moves: Vec<(Command, Board, u8, u32)> = simulator
+            .expand(&state.board, &packs[turn])
+            .map(|child| {
+                let obstacles = simulator::calculate_obstacle_count_from_chain_count(child.chain_count);
+                (Command::Drop((child.point, child.rotate_count)), child.board, child.chain_count, obstacles)
+            })
+            .collect();
+        moves.sort_by_key(|&(_, _, chain_count, _)| std::cmp::Reverse(chain_count));
+        if state.skill_point >= SPELL_SKILL_POINT {
+            let mut board = state.board;
+            let (erased_block_count, chain_count) = simulator.spell(&mut board);
+            let skill_score = if erased_block_count > 0 { simulator::calculate_skill_score(erased_block_count) } else { 0 };
+            let obstacles = simulator::calculate_obstacle_count(simulator::calculate_game_score(chain_count), skill_score);
+            moves.insert(0, (Command::Spell, board, chain_count, obstacles));
+        }
+        let mut turns = 1;
+        let mut unknown = false;
+        for (command, board, chain_count, obstacles) in moves {
+            if board.is_game_over() {
+                continue;
+            }
+            board.debug_check_invariants();
+            let skill_point = match command {
+                Command::Spell => 0,
+                Command::Drop(_) if chain_count > 0 => std::cmp::min(MAX_SKILL_POINT, state.skill_point + CHAIN_SKILL_POINT),
+                Command::Drop(_) => state.skill_point,
+            };
+            let next = EnemyState {
+                board,
+                obstacle_block_count: state.obstacle_block_count.saturating_sub(obstacles) + incoming,
+                skill_point: skill_point.saturating_sub(skill_reduce),
+            };
+            //the game ends or no line is left to fall
+            if next.obstacle_block_count < FIELD_WIDTH as u32 || turn + 1 >= packs.len() {
+                return Lethal::Escape(command);
+            }
+            match self.search(simulator, packs, turn + 1, next, 0, 0) {
+                Lethal::Escape(_) => return Lethal::Escape(command),
+                Lethal::Dies(child_turns) => turns = std::cmp::max(turns, child_turns + 1),
+                Lethal::Unknown => unknown = true,
+            }
+        }
+        if unknown {
+            return Lethal::Unknown;
+        }
+        if incoming == 0 {
+            self.dead.insert(key, turns);
+        }
+        Lethal::Dies(turns)
+    }
+}
+
+#[test]
+fn test_lethal_check() {
+    let wall = crate::test_fixtures::wall();
+    let packs = vec![Pack::new(&[0, 1, 0, 0]).unique_rotations(); 10];
+    let mut simulator = Simulator::new();
+    let mut check = LethalCheck::default();
+    let player = GameStatus::default();
+    let enemy = GameStatus::default().with_board(wall);
+    //the block fits in the last column this turn, the line falls on the next
+    assert_eq!(check.check(&mut simulator, &packs, 0, &enemy, 10, 0), Lethal::Dies(2));
+    assert!(check.check_chain(&mut simulator, &packs, 0, &player, &enemy, 8).is_lethal());
+    //less than a line never falls
+    assert!(matches!(check.check(&mut simulator, &packs, 0, &enemy, 9, 0), Lethal::Escape(_)));
+    //the player's own obstacles are offset first
+    let buried = player.clone().with_obstacle_block_count(10);
+    assert!(!check.check_chain(&mut simulator, &packs, 0, &buried, &enemy, 8).is_lethal());
+    //pending obstacles of the enemy count too
+    let pending = enemy.clone().with_obstacle_block_count(5);
+    assert!(check.check(&mut simulator, &packs, 0, &pending, 5, 0).is_lethal());
+
+    //two lines from the top, the second line kills unless a chain offsets it
+    let board = crate::test_fixtures::two_lines_from_top();
+    let enemy = GameStatus::default().with_board(board);
+    let ones = vec![Pack::new(&[1, 1, 0, 0]).unique_rotations(); 10];
+    assert_eq!(check.check(&mut simulator, &ones, 0, &enemy, 20, 0), Lethal::Dies(3));
+    //a 7 next to the 3 after the first line makes 2 chains which offset 1 obstacle
+    let mut packs = ones.clone();
+    packs[1] = Pack::new(&[7, 0, 0, 0]).unique_rotations();
+    assert!(matches!(check.check(&mut simulator, &packs, 0, &enemy, 20, 0), Lethal::Escape(_)));
+    assert!(check.check(&mut simulator, &packs, 0, &enemy, 30, 0).is_lethal());
+    //an empty board outlasts a few lines
+    let enemy = GameStatus::default();
+    assert!(matches!(check.check(&mut simulator, &ones, 0, &enemy, 40, 0), Lethal::Escape(_)));
+    let mut check = check.with_node_limit(1);
+    assert_eq!(check.check(&mut simulator, &ones, 0, &enemy, 40, 0), Lethal::Unknown);
+}
@@ -5,10 +5,29 @@ pub mod search_state;
 pub mod command;
 pub mod evaluation;
 pub mod xorshift;
-pub mod scanner;
 pub mod solver;
 pub mod game_status;
 pub mod solver_config;
 pub mod search_result;
 pub mod bit_board;
-pub mod zobrist_hash_table;
\ No newline at end of file
+pub mod zobrist_hash_table;
+pub mod match_record;
+pub mod viewer;
+pub mod html_export;
+pub mod referee;
+pub mod protocol;
+pub mod transcript;
+pub mod replay;
+pub mod reference_simulator;
+pub mod perft;
+pub mod bit_plane_board;
+pub mod beam;
+pub mod replacement_table;
+pub mod transposition;
+pub mod fire_timing;
+pub mod attack_schedule;
+pub mod counter;
+pub mod lethal;
+pub mod survival;
+#[cfg(test)]
+pub mod test_fixtures;
@@ -5,41 +5,83 @@ use clap::{SubCommand, ArgMatches};
 
 extern crate togatog_ai;
 
-use togatog_ai::scanner;
-use togatog_ai::solver::Solver;
-use togatog_ai::solver_config::{SolverConfig, SOLVER_VERSION};
+use togatog_ai::solver::{Solver, MAX_TURN};
+use togatog_ai::solver_config::{SearchStrategy, SolverConfig, DEFAULT_CHOKUDAI_WIDTH, SOLVER_VERSION};
 use togatog_ai::command::Command;
+use togatog_ai::board::Board;
+use togatog_ai::game_status::GameStatus;
+use togatog_ai::match_record::MatchRecord;
+use togatog_ai::pack::Pack;
+use togatog_ai::viewer;
+use togatog_ai::html_export;
+use togatog_ai::replay;
+use togatog_ai::perft;
+use togatog_ai::search_result::format_principal_variation;
+use togatog_ai::referee::{GameResult, Referee};
+use togatog_ai::simulator::Simulator;
+use togatog_ai::xorshift::Xorshift;
+use togatog_ai::protocol::{ProtocolError, ProtocolReader};
+use togatog_ai::transcript::{TeeReader, Transcript};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::io::BufRead;
 
-fn bench(pack: std::fs::File, info: std::fs::File, seed: u64, output_file: std::fs::File) {
-    let mut pack = scanner::Scanner { stdin: pack };
-    let mut information = scanner::Scanner { stdin: info };
+//read packs and only one turn of information
+fn read_files(pack: std::fs::File, info: std::fs::File) -> Result<(Vec<Pack>, usize, GameStatus, GameStatus), ProtocolError> {
+    let packs = ProtocolReader::new(std::io::BufReader::new(pack)).read_raw_packs()?;
+    let mut information = ProtocolReader::new(std::io::BufReader::new(info));
+    let current_turn = information.read_turn()?.ok_or_else(|| ProtocolError::UnexpectedEof { line: information.line(), expected: "a turn (0-499)" })?;
+    let player = information.read_game_status()?;
+    let enemy = information.read_game_status()?;
+    Ok((packs, current_turn, player, enemy))
+}
+
+//a missing or stale cache file only means a cold start
+fn load_cache(solver: &mut Solver, path: &str) {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(_) => return,
+    };
+    match solver.evaluate_cache_mut().load(&mut std::io::BufReader::new(file)) {
+        Ok(count) => eprintln!("Loaded {} cache entries from {}", count, path),
+        Err(e) => eprintln!("Ignored the cache file {}: {}", path, e),
+    }
+}
+
+fn save_cache(solver: &Solver, path: &str) {
+    let file = std::fs::File::create(path).expect("Can't create a file");
+    let mut writer = std::io::BufWriter::new(file);
+    solver.evaluate_cache().save(&mut writer).expect("Can't write a file");
+}
+
+fn bench(pack: std::fs::File, info: std::fs::File, seed: u64, output_file: std::fs::File, cache: Option<&str>, config: SolverConfig) {
+    let (packs, current_turn, player, enemy) = read_files(pack, info).unwrap_or_else(|e| panic!("Invalid input: {}", e));
 
     let mut solver = Solver::default().with_seed(seed);
-    solver.set_packs(Solver::read_packs(&mut pack));
+    solver.set_packs(packs);
 
-    //read information and think at only one turn
-    let current_turn: usize = information.read();
-    let player = Solver::read_game_status(&mut information);
-    let enemy = Solver::read_game_status(&mut information);
+    //think at only one turn
     solver.set_turn(current_turn);
     solver.set_game_status(player, enemy);
-    solver.set_config(SolverConfig::default().with_beam(15, 500));
+    solver.set_config(config);
+    if let Some(path) = cache {
+        load_cache(&mut solver, path);
+    }
     let best_result = solver.think();
+    if let Some(path) = cache {
+        save_cache(&solver, path);
+    }
 
     best_result.to_csv(output_file).unwrap();
 }
 
 fn profile(pack: std::fs::File, info: std::fs::File, seed: u64) {
-    let mut pack = scanner::Scanner { stdin: pack };
-    let mut information = scanner::Scanner { stdin: info };
+    let (packs, current_turn, player, enemy) = read_files(pack, info).unwrap_or_else(|e| panic!("Invalid input: {}", e));
 
     let mut solver = Solver::default().with_seed(seed);
 
-    solver.set_packs(Solver::read_packs(&mut pack));
-    //read information and think at only one turn
-    let current_turn: usize = information.read();
-    let player = Solver::read_game_status(&mut information);
-    let enemy = Solver::read_game_status(&mut information);
+    solver.set_packs(packs);
+    //think at only one turn
     solver.set_turn(current_turn);
     solver.set_game_status(player, enemy);
     solver.set_config(SolverConfig::default().with_beam(15, 500));
@@ -47,7 +89,246 @@ fn profile(pack: std::fs::File, info: std::fs::File, seed: u64) {
     eprintln!("{:?}", best_result);
 }
 
+fn view(frames: Vec<viewer::Frame>, speed: Option<u64>, color: bool) {
+    if frames.is_empty() {
+        return;
+    }
+    if let Some(speed) = speed {
+        for frame in frames.iter() {
+            print!("{}{}", viewer::CLEAR_SCREEN, viewer::render_frame(frame, color));
+            std::thread::sleep(std::time::Duration::from_millis(speed));
+        }
+        return;
+    }
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut idx = 0;
+    loop {
+        print!("{}{}", viewer::CLEAR_SCREEN, viewer::render_frame(&frames[idx], color));
+        println!("[{}/{}] enter: next, b: back, t <turn>: jump, q: quit", idx + 1, frames.len());
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => return,
+        };
+        let line = line.trim();
+        if line == "q" {
+            return;
+        } else if line == "b" {
+            idx = idx.saturating_sub(1);
+        } else if line.starts_with('t') {
+            if let Ok(turn) = line[1..].trim().parse::<usize>() {
+                if let Some(i) = frames.iter().position(|frame| frame.turn >= turn) {
+                    idx = i;
+                }
+            }
+        } else if idx + 1 < frames.len() {
+            idx += 1;
+        }
+    }
+}
+
+fn export_html(mut record: MatchRecord, title: &str, think: bool, seed: u64, output: &mut std::fs::File) {
+    if think {
+        //run the solver on every turn to annotate its principal variation
+        let mut solver = Solver::default().with_seed(seed);
+        solver.set_packs(record.packs.clone());
+        for turn_record in record.turns.iter_mut() {
+            solver.set_turn(turn_record.turn);
+            solver.set_game_status(turn_record.player.clone(), turn_record.enemy.clone());
+            let best_result = solver.think();
+            if turn_record.principal_variation().is_none() {
+                turn_record.notes.push(format!("pv {}", format_principal_variation(&best_result.principal_variation)));
+            }
+        }
+    }
+    use std::io::Write;
+    output.write_all(html_export::export_html(&record, title).as_bytes()).expect("Can't write a file");
+}
+
+//packs with 3 or 4 blocks like the real game
+fn random_packs(seed: u64) -> Vec<Pack> {
+    let mut rnd = Xorshift::with_seed(seed);
+    (0..MAX_TURN)
+        .map(|_| {
+            let mut blocks = [0; 4].map(|_| rnd.rand(9) as u8 + 1);
+            if rnd.rand(2) == 0 {
+                blocks[rnd.rand(4) as usize] = 0;
+            }
+            Pack::new(&blocks)
+        })
+        .collect()
+}
+
+//a human plays against the solver
+fn play(packs: Vec<Pack>, config: SolverConfig, seed: u64, color: bool, cache: Option<&str>) {
+    let mut solver = Solver::default().with_seed(seed);
+    solver.set_config(config);
+    if let Some(path) = cache {
+        load_cache(&mut solver, path);
+    }
+    play_game(&mut solver, packs, color);
+    if let Some(path) = cache {
+        save_cache(&solver, path);
+    }
+}
+
+fn play_game(solver: &mut Solver, packs: Vec<Pack>, color: bool) {
+    solver.set_packs(packs.clone());
+    let mut simulator = Simulator::new();
+    let mut referee = Referee::new(packs);
+    //referees before each turn to undo
+    let mut history: Vec<Referee> = Vec::new();
+    let mut message = String::new();
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        let turn = referee.turn();
+        let mut stages = viewer::turn_stages(&mut simulator, referee.player(0), &referee.pack(), None);
+        let mut enemy_stages = viewer::turn_stages(&mut simulator, referee.player(1), &referee.pack(), None);
+        let frame = viewer::Frame { turn, player: stages.remove(0), enemy: Some(enemy_stages.remove(0)) };
+        print!("{}{}", viewer::CLEAR_SCREEN, viewer::render_frame(&frame, color));
+        println!("{}", message);
+        if let Some(result) = referee.result() {
+            match result {
+                GameResult::Win(0) => println!("You win!"),
+                GameResult::Win(_) => println!("You lose."),
+                GameResult::Draw => println!("Draw."),
+            }
+            return;
+        }
+        let end = std::cmp::min(turn + 3, referee.packs().len());
+        print!("packs\n{}", viewer::render_packs(&referee.packs()[turn..end]));
+        println!("command (\"point rotation\" or \"S\"), u: undo, q: quit");
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => return,
+        };
+        let line = line.trim();
+        if line == "q" {
+            return;
+        }
+        if line == "u" {
+            message = match history.pop() {
+                Some(previous) => {
+                    referee = previous;
+                    "undone".to_string()
+                }
+                None => "nothing to undo".to_string(),
+            };
+            continue;
+        }
+        let command: Command = match line.parse() {
+            Ok(command) => command,
+            Err(e) => {
+                message = e;
+                continue;
+            }
+        };
+        if !referee.is_valid(0, command) {
+            message = format!("can't play {}", command);
+            continue;
+        }
+        solver.set_turn(turn);
+        solver.set_game_status(referee.player(1).clone(), referee.player(0).clone());
+        let enemy_command = solver.think().command;
+        history.push(referee.clone());
+        let outcomes = referee.step(&mut simulator, [command, enemy_command]);
+        message = format!(
+            "you: {} chain {} sent {}, solver: {} chain {} sent {}",
+            command,
+            outcomes[0].chain_count,
+            outcomes[0].sent_obstacle_block_count,
+            enemy_command,
+            outcomes[1].chain_count,
+            outcomes[1].sent_obstacle_block_count
+        );
+    }
+}
+
 fn run(matches: ArgMatches) {
+    if let Some(matches) = matches.subcommand_matches("perft") {
+        let pack = std::fs::File::open(matches.value_of("pack").expect("Invalid for pack file")).expect("Can't open a file");
+        let packs = ProtocolReader::new(std::io::BufReader::new(pack)).read_packs().unwrap_or_else(|e| panic!("Invalid pack file: {}", e));
+        let board = match matches.value_of("board") {
+            Some(path) => {
+                let board = std::fs::read_to_string(path).expect("Can't open a file");
+                board.parse().unwrap_or_else(|e| panic!("Invalid board: {}", e))
+            }
+            None => Board::default(),
+        };
+        let turn: usize = matches.value_of("turn").unwrap().parse().expect("Invalid turn");
+        let depth: usize = matches.value_of("depth").unwrap().parse().expect("Invalid depth");
+        let start = std::time::Instant::now();
+        let result = perft::perft(&board, &packs, turn, depth);
+        let elapsed = start.elapsed();
+        println!("{}", result);
+        println!("time: {} ms ({:.0} nodes/s)", elapsed.as_millis(), result.node_count() as f64 / elapsed.as_secs_f64());
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("replay") {
+        let text = std::fs::read_to_string(matches.value_of("record").expect("Invalid for record file")).expect("Can't open a file");
+        let record = MatchRecord::parse(&text).unwrap_or_else(|e| panic!("Invalid match record: {}", e));
+        //the seed the game was played with unless given
+        let seed: u64 = matches.value_of("seed").or_else(|| record.note("seed")).unwrap_or("1024").parse().expect("Invalid seed");
+        let diffs = replay::replay_record(&record, seed, SolverConfig::default(), |turn, result| {
+            eprintln!("turn {}: {}", turn, result.command);
+        });
+        for diff in diffs.iter() {
+            println!("{}", diff);
+        }
+        let recorded = record.turns.iter().filter(|turn_record| turn_record.command.is_some()).count();
+        println!("{} of {} turns differ", diffs.len(), recorded);
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("play") {
+        let seed: u64 = matches.value_of("seed").unwrap().parse().unwrap();
+        let packs = match matches.value_of("pack") {
+            Some(path) => {
+                let pack = std::fs::File::open(path).expect("Can't open a file");
+                ProtocolReader::new(std::io::BufReader::new(pack)).read_raw_packs().unwrap_or_else(|e| panic!("Invalid pack file: {}", e))
+            }
+            None => random_packs(seed),
+        };
+        let depth: usize = matches.value_of("depth").unwrap().parse().expect("Invalid depth");
+        let width: usize = matches.value_of("width").unwrap().parse().expect("Invalid width");
+        let config = SolverConfig::default()
+            .with_beam(depth, width)
+            .with_counter_search(matches.is_present("counter"))
+            .with_survival_search(matches.is_present("survival"))
+            .with_lethal_check(matches.is_present("lethal"));
+        play(packs, config, seed, !matches.is_present("no-color"), matches.value_of("cache"));
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("export-html") {
+        let path = matches.value_of("record").expect("Invalid for record file");
+        let text = std::fs::read_to_string(path).expect("Can't open a file");
+        let record = MatchRecord::parse(&text).unwrap_or_else(|e| panic!("Invalid match record: {}", e));
+        let mut output = std::fs::File::create(matches.value_of("output").expect("Invalid for output file")).expect("Can't create a file");
+        let seed: u64 = matches.value_of("seed").unwrap().parse().unwrap();
+        export_html(record, path, matches.is_present("think"), seed, &mut output);
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("view") {
+        let speed: Option<u64> = matches.value_of("speed").map(|v| v.parse().expect("Invalid speed"));
+        let color = !matches.is_present("no-color");
+        let frames = if let Some(record) = matches.value_of("record") {
+            let text = std::fs::read_to_string(record).expect("Can't open a file");
+            let record = MatchRecord::parse(&text).unwrap_or_else(|e| panic!("Invalid match record: {}", e));
+            viewer::record_frames(&record)
+        } else {
+            let board = std::fs::read_to_string(matches.value_of("board").expect("Invalid for board file")).expect("Can't open a file");
+            let board: Board = board.parse().unwrap_or_else(|e| panic!("Invalid board: {}", e));
+            let blocks: Vec<u8> = matches.value_of("pack").expect("Invalid for pack").split_whitespace().map(|v| v.parse().expect("Invalid pack")).collect();
+            assert_eq!(blocks.len(), 4, "A pack has 4 blocks");
+            let pack = Pack::new(&[blocks[0], blocks[1], blocks[2], blocks[3]]);
+            let command: Command = matches.value_of("command").expect("Invalid for command").parse().unwrap_or_else(|e| panic!("{}", e));
+            let obstacle_block_count: u32 = matches.value_of("obstacles").unwrap().parse().expect("Invalid obstacles");
+            let game_status = GameStatus::default().with_board(board).with_obstacle_block_count(obstacle_block_count);
+            viewer::board_frames(&game_status, &pack, command)
+        };
+        view(frames, speed, color);
+        return;
+    }
 
     if let Some(matches) = matches.subcommand_matches("profile") {
         let pack = std::fs::File::open(matches.value_of("pack").expect("Invalid for pack file")).expect("Can't open a file");
@@ -61,7 +342,19 @@ fn run(matches: ArgMatches) {
         let info = std::fs::File::open(matches.value_of("info").expect("Invalid for information file")).expect("Can't open a file");
         let output = std::fs::File::create(matches.value_of("output").expect("Invalid for output file")).expect("Can't create a file");
         let seed: u64 = matches.value_of("seed").unwrap().parse().unwrap();
-        bench(pack, info, seed, output);
+        let strategy = match matches.value_of("chokudai") {
+            Some(milliseconds) => SearchStrategy::Chokudai {
+                width: DEFAULT_CHOKUDAI_WIDTH,
+                time_limit: std::time::Duration::from_millis(milliseconds.parse().expect("Invalid milliseconds")),
+                node_limit: matches.value_of("nodes").map_or(usize::MAX, |nodes| nodes.parse().expect("Invalid nodes")),
+            },
+            None => SearchStrategy::Beam,
+        };
+        let config = SolverConfig::default()
+            .with_beam(15, 500)
+            .with_search_strategy(strategy)
+            .with_enemy_attack_prediction(matches.is_present("predict-attacks"));
+        bench(pack, info, seed, output, matches.value_of("cache"), config);
         return;
     }
     let seed: u64 = matches.value_of("seed").unwrap().parse().unwrap();
@@ -72,25 +365,83 @@ fn run(matches: ArgMatches) {
         eprintln!("togatog_ai_{}", SOLVER_VERSION);
         eprintln!("seed: {}", seed);
     }
-    println!("togatog_ai_{}", SOLVER_VERSION);
+    let cache = matches.value_of("cache");
+    let name = format!("togatog_ai_{}", SOLVER_VERSION);
+    println!("{}", name);
     let s = std::io::stdin();
-    let mut sc = scanner::Scanner { stdin: s.lock() };
+    let result = match matches.value_of("record") {
+        Some(path) => {
+            let file = std::fs::File::create(path).expect("Can't create a file");
+            let transcript = Rc::new(RefCell::new(Transcript::new(std::io::BufWriter::new(file))));
+            {
+                let mut transcript = transcript.borrow_mut();
+                transcript.command(&name).expect("Can't write a file");
+                transcript.note("seed", &seed.to_string()).expect("Can't write a file");
+            }
+            let reader = ProtocolReader::new(TeeReader::new(s.lock(), transcript.clone()));
+            solve(reader, seed, debug, Some(transcript), cache)
+        }
+        None => solve(ProtocolReader::new(s.lock()), seed, debug, None, cache),
+    };
+    if let Err(e) = result {
+        eprintln!("Invalid input: {}", e);
+        std::process::exit(1);
+    }
+}
+
+type Record = Rc<RefCell<Transcript<std::io::BufWriter<std::fs::File>>>>;
+
+//answer every turn until the input ends
+//a floating block in the input is a bug of the game or a hand-written record
+fn read_game_status<R: BufRead>(reader: &mut ProtocolReader<R>, debug: bool) -> Result<GameStatus, ProtocolError> {
+    let line = reader.line() + 1;
+    let (game_status, stability) = reader.read_game_status_with_stability()?;
+    if debug && !stability.is_stable() {
+        eprintln!(
+            "line {}: {} floating blocks fell, {} chains pending",
+            line, stability.fallen_block_count, stability.pending_chain_count
+        );
+    }
+    Ok(game_status)
+}
+
+fn solve<R: BufRead>(mut reader: ProtocolReader<R>, seed: u64, debug: bool, record: Option<Record>, cache: Option<&str>) -> Result<(), ProtocolError> {
     //create a default solver object
     let mut solver = Solver::default().with_seed(seed);
     //set debug option
     solver.set_debug(debug);
+    if let Some(path) = cache {
+        load_cache(&mut solver, path);
+    }
     //read and set packs
-    solver.set_packs(Solver::read_packs(&mut sc));
-    loop {
-        let current_turn: usize = sc.read();
+    if reader.is_eof()? {
+        return Ok(());
+    }
+    solver.set_packs(reader.read_raw_packs()?);
+    while let Some(current_turn) = reader.read_turn()? {
         solver.set_turn(current_turn);
         //read player data
-        let player = Solver::read_game_status(&mut sc);
-        let enemy = Solver::read_game_status(&mut sc);
+        let player = read_game_status(&mut reader, debug)?;
+        let enemy = read_game_status(&mut reader, debug)?;
         solver.set_game_status(player, enemy);
+        let start = std::time::Instant::now();
         let best_result = solver.think();
+        let think_milliseconds = start.elapsed().as_millis();
         Solver::output_command(best_result.command);
+        if let Some(record) = record.as_ref() {
+            let mut transcript = record.borrow_mut();
+            let received_milliseconds = transcript.elapsed_milliseconds() - think_milliseconds;
+            //the game must go on even if the disk is full
+            let _ = transcript.note("time_ms", &received_milliseconds.to_string())
+                .and_then(|_| transcript.note("think_ms", &think_milliseconds.to_string()))
+                .and_then(|_| transcript.note("pv", &format_principal_variation(&best_result.principal_variation)))
+                .and_then(|_| transcript.command(&best_result.command.to_string()));
+        }
     }
+    if let Some(path) = cache {
+        save_cache(&solver, path);
+    }
+    Ok(())
 }
 
 fn main() {
@@ -103,12 +454,54 @@ fn main() {
             .arg(clap::Arg::with_name("info").help("The path of an information file").short("i").long("info").value_name("INFORMATION").required(true))
             .arg(clap::Arg::with_name("output").help("The path of an output csv file").short("o").long("output").value_name("OUTPUT").required(true))
             .arg(clap::Arg::with_name("seed").short("s").long("seed").help("seed for generating random number").default_value("28"))
+            .arg(clap::Arg::with_name("cache").help("Load the evaluation cache from a file and save it after the run").long("cache").value_name("CACHE"))
+            .arg(clap::Arg::with_name("chokudai").help("Run Chokudai search for the milliseconds instead of the beam search").long("chokudai").value_name("MILLISECONDS"))
+            .arg(clap::Arg::with_name("nodes").help("Stop Chokudai search after expanding the states").long("nodes").value_name("NODES").requires("chokudai"))
+            .arg(clap::Arg::with_name("predict-attacks").help("Apply the enemy attack predicted by a beam on the enemy board to the search").long("predict-attacks"))
         )
         .subcommand(SubCommand::with_name("profile").about("Run for profiler")
             .arg(clap::Arg::with_name("pack").help("The path of a pack file").short("p").long("pack").value_name("PACK").required(true))
             .arg(clap::Arg::with_name("info").help("The path of an information file").short("i").long("info").value_name("INFORMATION").required(true))
         )
+        .subcommand(SubCommand::with_name("view").about("Step through a match record or a command on a board")
+            .arg(clap::Arg::with_name("record").help("The path of a match record").short("r").long("record").value_name("RECORD").required_unless("board"))
+            .arg(clap::Arg::with_name("board").help("The path of a board file").short("b").long("board").value_name("BOARD").conflicts_with("record").requires_all(&["pack", "command"]))
+            .arg(clap::Arg::with_name("pack").help("Pack blocks, e.g. \"1 2 3 4\"").short("p").long("pack").value_name("PACK"))
+            .arg(clap::Arg::with_name("command").help("Command, e.g. \"3 1\" or \"S\"").short("c").long("command").value_name("COMMAND"))
+            .arg(clap::Arg::with_name("obstacles").help("Pending obstacle blocks").long("obstacles").value_name("OBSTACLES").default_value("0"))
+            .arg(clap::Arg::with_name("speed").help("Auto-play with a delay in milliseconds per frame").long("speed").value_name("MILLISECONDS"))
+            .arg(clap::Arg::with_name("no-color").long("no-color").help("Don't colour blocks"))
+        )
+        .subcommand(SubCommand::with_name("export-html").about("Export a match record as an offline HTML replay")
+            .arg(clap::Arg::with_name("record").help("The path of a match record").short("r").long("record").value_name("RECORD").required(true))
+            .arg(clap::Arg::with_name("output").help("The path of an output html file").short("o").long("output").value_name("OUTPUT").required(true))
+            .arg(clap::Arg::with_name("think").long("think").help("Run the solver on every turn to annotate principal variations"))
+            .arg(clap::Arg::with_name("seed").short("s").long("seed").help("seed for generating random number").default_value("1024"))
+        )
+        .subcommand(SubCommand::with_name("play").about("Play against the solver in the terminal")
+            .arg(clap::Arg::with_name("pack").help("The path of a pack file, random packs if omitted").short("p").long("pack").value_name("PACK"))
+            .arg(clap::Arg::with_name("seed").short("s").long("seed").help("seed for random packs and the solver").default_value("1024"))
+            .arg(clap::Arg::with_name("depth").long("depth").help("beam depth of the solver").value_name("DEPTH").default_value("10"))
+            .arg(clap::Arg::with_name("width").long("width").help("beam width of the solver").value_name("WIDTH").default_value("300"))
+            .arg(clap::Arg::with_name("cache").help("Load the evaluation cache from a file and save it after the game").long("cache").value_name("CACHE"))
+            .arg(clap::Arg::with_name("counter").long("counter").help("Let the solver answer incoming obstacles with the counter search"))
+            .arg(clap::Arg::with_name("survival").long("survival").help("Let the solver play the survival search near the danger line"))
+            .arg(clap::Arg::with_name("lethal").long("lethal").help("Let the solver fire right away when the lethal check proves the chain kills"))
+            .arg(clap::Arg::with_name("no-color").long("no-color").help("Don't colour blocks"))
+        )
+        .subcommand(SubCommand::with_name("perft").about("Count every move sequence from a board")
+            .arg(clap::Arg::with_name("pack").help("The path of a pack file").short("p").long("pack").value_name("PACK").required(true))
+            .arg(clap::Arg::with_name("board").help("The path of a board file, an empty board if omitted").short("b").long("board").value_name("BOARD"))
+            .arg(clap::Arg::with_name("turn").short("t").long("turn").help("the turn of the first pack").value_name("TURN").default_value("0"))
+            .arg(clap::Arg::with_name("depth").short("d").long("depth").help("the number of moves").value_name("DEPTH").default_value("3"))
+        )
+        .subcommand(SubCommand::with_name("replay").about("Replay a recorded game and report the turns whose command changed")
+            .arg(clap::Arg::with_name("record").help("The path of a match record").short("r").long("record").value_name("RECORD").required(true))
+            .arg(clap::Arg::with_name("seed").short("s").long("seed").help("seed for generating random number, the recorded seed if omitted").value_name("SEED"))
+        )
+        .arg(clap::Arg::with_name("record").short("r").long("record").value_name("RECORD").help("write a transcript of the game to a file"))
         .arg(clap::Arg::with_name("debug").short("d").long("debug").help("print debug information verbosely"))
+        .arg(clap::Arg::with_name("cache").long("cache").value_name("CACHE").help("load the evaluation cache from a file and save it when the input ends"))
         .arg(clap::Arg::with_name("seed").short("s").long("seed").help("seed for generating random number").default_value("1024"))
         .get_matches();
     std::thread::Builder::new()
@@ -0,0 +1,206 @@
+use crate::board::FIELD_HEIGHT;
+use crate::command::Command;
+use crate::game_status::GameStatus;
+use crate::pack::Pack;
+use crate::protocol::{ProtocolError, ProtocolReader};
+use crate::referee::SPELL_SKILL_POINT;
+use crate::search_result::parse_principal_variation;
+use crate::simulator::Simulator;
+
+//A match record is the game input as read from stdin, together with
+//the lines written to stdout prefixed by '>'. Lines starting with '#' are comments.
+//
+//> togatog_ai_4.0
+//(packs)
+//(turn, player and enemy status)
+//> 3 1
+//(turn, player and enemy status)
+//> S
+//
+//Comments after a turn's input are notes on the turn such as "# pv 3 1, 4 0".
+pub const COMMAND_PREFIX: char = '>';
+pub const COMMENT_PREFIX: char = '#';
+
+//the value of a "key value" note
+fn find_note<'a>(notes: &'a [String], key: &str) -> Option<&'a str> {
+    notes.iter().find_map(|note| {
+        let mut tokens = note.splitn(2, ' ');
+        if tokens.next() == Some(key) {
+            Some(tokens.next().unwrap_or("").trim())
+        } else {
+            None
+        }
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct TurnRecord {
+    pub turn: usize,
+    pub player: GameStatus,
+    pub enemy: GameStatus,
+    //the command player wrote in this turn
+    pub command: Option<Command>,
+    //comments written in this turn
+    pub notes: Vec<String>,
+}
+
+impl TurnRecord {
+    pub fn note(&self, key: &str) -> Option<&str> {
+        find_note(&self.notes, key)
+    }
+    pub fn principal_variation(&self) -> Option<Vec<Command>> {
+        self.note("pv")
+            .and_then(|pv| parse_principal_variation(pv).ok())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchRecord {
+    pub packs: Vec<Pack>,
+    pub turns: Vec<TurnRecord>,
+    //comments written before the first turn such as "# seed 1024"
+    pub notes: Vec<String>,
+}
+
+impl MatchRecord {
+    pub fn parse(text: &str) -> Result<MatchRecord, String> {
+        //protocol input and the offsets where each command was written
+        let mut input = String::new();
+        let mut commands: Vec<(usize, &str)> = Vec::new();
+        let mut comments: Vec<(usize, &str)> = Vec::new();
+        for line in text.lines() {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with(COMMAND_PREFIX) {
+                commands.push((input.len(), trimmed[1..].trim()));
+            } else if trimmed.starts_with(COMMENT_PREFIX) {
+                comments.push((input.len(), trimmed[1..].trim()));
+            } else {
+                input.push_str(line);
+                input.push('\n');
+            }
+        }
+        let mut reader = ProtocolReader::new(input.as_bytes());
+        let to_string = |e: ProtocolError| e.to_string();
+        if reader.is_eof().map_err(to_string)? {
+            return Err("empty match record".to_string());
+        }
+        let packs = reader.read_raw_packs().map_err(to_string)?;
+        let mut turns = Vec::new();
+        let mut ends = Vec::new();
+        while let Some(turn) = reader.read_turn().map_err(to_string)? {
+            let player = reader.read_game_status().map_err(to_string)?;
+            let enemy = reader.read_game_status().map_err(to_string)?;
+            ends.push(reader.offset());
+            turns.push(TurnRecord { turn, player, enemy, command: None, notes: Vec::new() });
+        }
+        //commands and comments belong to the last turn read before they were written
+        for (i, turn_record) in turns.iter_mut().enumerate() {
+            let next_end = ends.get(i + 1).cloned().unwrap_or(usize::MAX);
+            let in_turn = |&&(offset, _): &&(usize, &str)| offset >= ends[i] && offset < next_end;
+            turn_record.notes = comments
+                .iter()
+                .filter(in_turn)
+                .map(|&(_, comment)| comment.to_string())
+                .collect();
+            if let Some(&(_, command)) = commands.iter().find(in_turn) {
+                turn_record.command = Some(
+                    command
+                        .parse()
+                        .map_err(|e| format!("turn {}: {}", turn_record.turn, e))?,
+                );
+            }
+        }
+        let first_end = ends.first().cloned().unwrap_or(usize::MAX);
+        let notes = comments
+            .iter()
+            .filter(|&&(offset, _)| offset < first_end)
+            .map(|&(_, comment)| comment.to_string())
+            .collect();
+        Ok(MatchRecord { packs, turns, notes })
+    }
+    pub fn note(&self, key: &str) -> Option<&str> {
+        find_note(&self.notes, key)
+    }
+    pub fn pack(&self, turn: usize) -> Pack {
+        self.packs[turn]
+    }
+    //find a command which turns the enemy board into the next turn's board
+    pub fn infer_enemy_command(&self, index: usize, simulator: &mut Simulator) -> Option<Command> {
+        let current = &self.turns[index];
+        let next = self.turns.get(index + 1)?;
+        let mut board = current.enemy.board();
+        if current.enemy.obstacle_block_count() >= 10 {
+            board.drop_obstacles();
+        }
+        let target = next.enemy.board();
+        for (pack, rotate_count) in self.pack(current.turn).unique_rotations() {
+            for point in 0..9 {
+                let mut next_board = board;
+                if next_board.heights[point] + 2 > FIELD_HEIGHT
+                    || next_board.heights[point + 1] + 2 > FIELD_HEIGHT
+                {
+                    continue;
+                }
+                simulator.simulate(&mut next_board, point, &pack);
+                if next_board == target {
+                    return Some(Command::Drop((point, rotate_count)));
+                }
+            }
+        }
+        if current.enemy.skill_point() >= SPELL_SKILL_POINT {
+            let mut next_board = board;
+            simulator.spell(&mut next_board);
+            if next_board == target {
+                return Some(Command::Spell);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+pub fn sample_record_text() -> String {
+    let mut text = String::from("> togatog_ai_4.0\n");
+    text.push_str("0 0\n9 4\nEND\n");
+    for i in 1..crate::solver::MAX_TURN {
+        text.push_str(&format!("{} {}\n{} {}\nEND\n", i % 9 + 1, (i + 1) % 9 + 1, 0, (i + 3) % 9 + 1));
+    }
+    let empty_board = "0 0 0 0 0 0 0 0 0 0\n".repeat(16);
+    let mut one_block_board = "0 0 0 0 0 0 0 0 0 0\n".repeat(15);
+    one_block_board.push_str("0 0 0 0 0 0 0 0 9 4\n");
+    text.push_str(&format!(
+        "0\n180000\n0\n0\n0\n{}END\n180000\n0\n0\n0\n{}END\n",
+        empty_board, empty_board
+    ));
+    text.push_str("> 8 0\n# pv 8 0, 0 1\n# think_ms 1000\n");
+    text.push_str(&format!(
+        "1\n179000\n0\n0\n0\n{}END\n179500\n0\n0\n0\n{}END\n",
+        one_block_board, one_block_board
+    ));
+    text.push_str("> 0 1\n");
+    text
+}
+
+#[test]
+fn test_parse_match_record() {
+    let record = MatchRecord::parse(&sample_record_text()).unwrap();
+    assert_eq!(record.packs.len(), crate::solver::MAX_TURN);
+    assert_eq!(record.pack(1), Pack::new(&[2, 3, 0, 5]));
+    assert_eq!(record.turns.len(), 2);
+    assert_eq!(record.turns[0].turn, 0);
+    assert_eq!(record.turns[0].command, Some(Command::Drop((8, 0))));
+    assert_eq!(record.turns[1].command, Some(Command::Drop((0, 1))));
+    assert_eq!(record.turns[0].note("think_ms"), Some("1000"));
+    assert_eq!(
+        record.turns[0].principal_variation(),
+        Some(vec![Command::Drop((8, 0)), Command::Drop((0, 1))])
+    );
+    assert_eq!(record.turns[1].notes.len(), 0);
+    assert_eq!(record.turns[1].player.rest_time_milliseconds(), 179000);
+    assert_eq!(record.turns[1].enemy.board().get(0, 8), 9);
+
+    let mut simulator = Simulator::new();
+    assert_eq!(record.infer_enemy_command(0, &mut simulator), Some(Command::Drop((8, 0))));
+    assert_eq!(record.infer_enemy_command(1, &mut simulator), None);
+    assert!(MatchRecord::parse("").is_err());
+}
@@ -79,6 +79,28 @@ impl Pack {
             self.rotate();
         }
     }
+    //the pack as it falls with a command's rotate count
+    pub fn rotated(&self, rotate_count: usize) -> Pack {
+        let mut pack = *self;
+        pack.rotates(rotate_count);
+        pack.drop();
+        pack
+    }
+    //(dropped pack, rotate count) without duplicated shapes
+    pub fn unique_rotations(&self) -> Vec<(Pack, usize)> {
+        let mut res: Vec<(Pack, usize)> = Vec::new();
+        for i in 0..4 {
+            //To make pack unique
+            //5 8  0 8
+            //0 5  5 5
+            let pack = self.rotated(i);
+            if res.iter().any(|&(p, _)| p == pack) {
+                continue;
+            }
+            res.push((pack, i));
+        }
+        res
+    }
     fn rotate(&mut self) {
         let tmp1 = self.get(0);
         let tmp2 = self.get(1);
@@ -144,4 +166,16 @@ fn drop() {
     let mut p = Pack::new(&[1, 2, 3, 4]);
     p.drop();
     debug_assert_eq!(p.vec(), [1, 2, 3, 4]);
-}
\ No newline at end of file
+}
+#[test]
+fn test_unique_rotations() {
+    let rotations = Pack::new(&[5, 0, 8, 0]).unique_rotations();
+    debug_assert_eq!(rotations.len(), 4);
+    debug_assert_eq!(rotations[1], (Pack::new(&[0, 0, 8, 5]), 1));
+    let rotations: Vec<usize> = Pack::new(&[5, 5, 0, 0])
+        .unique_rotations()
+        .iter()
+        .map(|&(_, rotate_count)| rotate_count)
+        .collect();
+    debug_assert_eq!(rotations, vec![0, 1, 3]);
+}
@@ -0,0 +1,251 @@
+use crate::board::Board;
+use crate::pack::Pack;
+use crate::simulator::{Child, Simulator};
+use crate::zobrist_hash_table::ZobristHash;
+
+//counts of every move sequence from a board
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PerftResult {
+    //boards after depth moves
+    pub leaf_count: u64,
+    //leaf boards by zobrist hash
+    pub distinct_board_count: u64,
+    //moves by the chain count they made, at every depth
+    pub chain_counts: Vec<u64>,
+    //moves which ended the game, not searched further
+    pub game_over_count: u64,
+}
+
+impl PerftResult {
+    pub fn node_count(&self) -> u64 {
+        self.chain_counts.iter().sum()
+    }
+}
+
+impl std::fmt::Display for PerftResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "leaves: {}", self.leaf_count)?;
+        writeln!(f, "distinct boards: {}", self.distinct_board_count)?;
+        writeln!(f, "game overs: {}", self.game_over_count)?;
+        for (chain_count, &count) in self.chain_counts.iter().enumerate() {
+            if chain_count > 0 && count > 0 {
+                writeln!(f, "chain {}: {}", chain_count, count)?;
+            }
+        }
+        write!(f, "nodes: {}", self.node_count())
+    }
+}
+
+struct Perft<'a> {
+    simulator: Simulator,
+    packs: &'a [Vec<(Pack, usize)>],
+    leaf_hashes: fnv::FnvHashSet<ZobristHash>,
+    result: PerftResult,
+}
+
+impl<'a> Perft<'a> {
+    fn search(&mut self, board: &Board, turn: usize, depth: usize) {
+        if depth == 0 {
+            self.result.leaf_count += 1;
+            self.leaf_hashes.insert(board.zobrist_hash());
+            return;
+        }
+        let children: Vec<Child> = self.simulator.expand(board, &self.packs[turn]).collect();
+        //no room for the pack
+        self.result.game_over_count += (self.packs[turn].len() * 9 - children.len()) as u64;
+        for child in children {
+            let chain_count = child.chain_count as usize;
+            if self.result.chain_counts.len() <= chain_count {
+                self.result.chain_counts.resize(chain_count + 1, 0);
+            }
+            self.result.chain_counts[chain_count] += 1;
+            if child.board.is_game_over() {
+                self.result.game_over_count += 1;
+                continue;
+            }
+            self.search(&child.board, turn + 1, depth - 1);
+        }
+    }
+}
+
+//every drop of every unique rotation for depth turns from turn
+pub fn perft(board: &Board, packs: &[Vec<(Pack, usize)>], turn: usize, depth: usize) -> PerftResult {
+    assert!(turn + depth <= packs.len());
+    let mut perft = Perft {
+        simulator: Simulator::new(),
+        packs,
+        leaf_hashes: fnv::FnvHashSet::default(),
+        result: PerftResult {
+            leaf_count: 0,
+            distinct_board_count: 0,
+            chain_counts: vec![0],
+            game_over_count: 0,
+        },
+    };
+    perft.search(board, turn, depth);
+    perft.result.distinct_board_count = perft.leaf_hashes.len() as u64;
+    perft.result
+}
+
+#[cfg(test)]
+fn test_packs(seed: u64) -> Vec<Vec<(Pack, usize)>> {
+    let mut rnd = crate::xorshift::Xorshift::with_seed(seed);
+    (0..crate::solver::MAX_TURN)
+        .map(|_| crate::reference_simulator::random_pack(&mut rnd).unique_rotations())
+        .collect()
+}
+
+//a stable board with some obstacles
+#[cfg(test)]
+fn test_board() -> Board {
+    use crate::reference_simulator;
+    let board: Board = "
+    ..........
+    ..........
+    ..........
+    ..........
+    ..........
+    ..........
+    ..........
+    ..........
+    ..........
+    ..........
+    ..........
+    ....#.....
+    ..7.4.....
+    .#3.6.8...
+    .41.32#.9.
+    #86#75431#
+    "
+    .parse()
+    .unwrap();
+    let mut grid = reference_simulator::from_board(&board);
+    reference_simulator::resolve_chains(&mut grid);
+    reference_simulator::to_board(&grid)
+}
+
+//columns close to the danger line
+#[cfg(test)]
+fn tall_board() -> Board {
+    "
+    ..........
+    #.........
+    #........#
+    #........#
+    #........#
+    #........#
+    #........#
+    #........#
+    #........#
+    #........#
+    #........#
+    #........#
+    #........#
+    #........#
+    #2...6...#
+    #1.3.5.7.#
+    "
+    .parse()
+    .unwrap()
+}
+
+//the same counting on the reference simulator
+#[cfg(test)]
+fn reference_perft(board: &Board, packs: &[Vec<(Pack, usize)>], turn: usize, depth: usize) -> PerftResult {
+    use crate::reference_simulator;
+    let mut result = PerftResult { leaf_count: 0, distinct_board_count: 0, chain_counts: vec![0], game_over_count: 0 };
+    let mut leaves = Vec::new();
+    let mut grids = vec![reference_simulator::from_board(board)];
+    for d in 0..depth {
+        let mut next_grids = Vec::new();
+        for grid in grids.iter() {
+            for &(pack, _) in packs[turn + d].iter() {
+                for point in 0..9 {
+                    let mut next_grid = *grid;
+                    let chain_count = match reference_simulator::simulate(&mut next_grid, point, &pack) {
+                        Some(chain_count) => chain_count as usize,
+                        None => {
+                            result.game_over_count += 1;
+                            continue;
+                        }
+                    };
+                    if result.chain_counts.len() <= chain_count {
+                        result.chain_counts.resize(chain_count + 1, 0);
+                    }
+                    result.chain_counts[chain_count] += 1;
+                    if reference_simulator::heights(&next_grid).iter().any(|&h| h >= crate::board::DANGER_LINE_HEIGHT) {
+                        result.game_over_count += 1;
+                        continue;
+                    }
+                    next_grids.push(next_grid);
+                }
+            }
+        }
+        grids = next_grids;
+    }
+    result.leaf_count = grids.len() as u64;
+    leaves.extend(grids);
+    leaves.sort();
+    leaves.dedup();
+    result.distinct_board_count = leaves.len() as u64;
+    result
+}
+
+#[test]
+fn test_perft_reference() {
+    let packs = test_packs(3);
+    for board in [Board::default(), test_board(), tall_board()].iter() {
+        for depth in 1..=2 {
+            assert_eq!(perft(board, &packs, 0, depth), reference_perft(board, &packs, 0, depth));
+        }
+    }
+}
+
+#[test]
+fn test_perft() {
+    //regression numbers, they agree with the reference simulator
+    let packs = test_packs(3);
+    let result = perft(&Board::default(), &packs, 0, 3);
+    assert_eq!(result.leaf_count, 23328);
+    assert_eq!(result.distinct_board_count, 13738);
+    assert_eq!(result.chain_counts, vec![15236, 6486, 2266, 24]);
+    assert_eq!(result.game_over_count, 0);
+
+    let result = perft(&test_board(), &packs, 10, 3);
+    assert_eq!(result.leaf_count, 46656);
+    assert_eq!(result.distinct_board_count, 24020);
+    assert_eq!(result.chain_counts, vec![37913, 9372, 674, 29]);
+    assert_eq!(result.game_over_count, 0);
+
+    let result = perft(&tall_board(), &packs, 20, 3);
+    assert_eq!(result.leaf_count, 32676);
+    assert_eq!(result.distinct_board_count, 27135);
+    assert_eq!(result.chain_counts, vec![5974, 27386, 3936, 177, 3]);
+    assert_eq!(result.game_over_count, 3760);
+    assert_eq!(result.node_count(), 5974 + 27386 + 3936 + 177 + 3);
+}
+
+#[test]
+fn test_perft_pack_file() {
+    //regression numbers on a real pack file, they agree with the reference simulator
+    //input/test/pack_0000.pack is a copy of pack/pack_0000.pack in input/pack.tar.gz
+    let text = include_str!("../input/test/pack_0000.pack");
+    let packs = crate::protocol::ProtocolReader::new(text.as_bytes()).read_packs().unwrap();
+    let result = perft(&Board::default(), &packs, 0, 3);
+    assert_eq!(result.leaf_count, 46656);
+    assert_eq!(result.distinct_board_count, 40206);
+    assert_eq!(result.chain_counts, vec![38273, 9259, 426, 30]);
+    assert_eq!(result.game_over_count, 0);
+
+    let result = perft(&test_board(), &packs, 10, 3);
+    assert_eq!(result.leaf_count, 46656);
+    assert_eq!(result.distinct_board_count, 21097);
+    assert_eq!(result.chain_counts, vec![29095, 17867, 868, 158]);
+    assert_eq!(result.game_over_count, 0);
+
+    let result = perft(&tall_board(), &packs, 20, 3);
+    assert_eq!(result.leaf_count, 33642);
+    assert_eq!(result.distinct_board_count, 30504);
+    assert_eq!(result.chain_counts, vec![11757, 18606, 7611, 1699, 179]);
+    assert_eq!(result.game_over_count, 5104);
+}
@@ -0,0 +1,228 @@
+use crate::board::{Board, InputStability, FIELD_WIDTH, INPUT_FIELD_HEIGHT, OBSTACLE_BLOCK};
+use crate::game_status::GameStatus;
+use crate::pack::Pack;
+use crate::referee::MAX_SKILL_POINT;
+use crate::solver::MAX_TURN;
+use std::collections::VecDeque;
+use std::io::BufRead;
+
+const END_TOKEN: &str = "END";
+
+#[derive(Debug)]
+pub enum ProtocolError {
+    Io(std::io::Error),
+    //input ended in the middle of a message
+    UnexpectedEof { line: usize, expected: &'static str },
+    InvalidToken { line: usize, token: String, expected: &'static str },
+}
+
+impl std::fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ProtocolError::Io(e) => write!(f, "io error: {}", e),
+            ProtocolError::UnexpectedEof { line, expected } => {
+                write!(f, "line {}: expected {}, found end of input", line, expected)
+            }
+            ProtocolError::InvalidToken { line, token, expected } => {
+                write!(f, "line {}: expected {}, found {:?}", line, expected, token)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl From<std::io::Error> for ProtocolError {
+    fn from(e: std::io::Error) -> ProtocolError {
+        ProtocolError::Io(e)
+    }
+}
+
+//reads the game protocol line by line
+pub struct ProtocolReader<R> {
+    reader: R,
+    //line number of the tokens, 1-indexed
+    line: usize,
+    //bytes of the lines read so far
+    offset: usize,
+    tokens: VecDeque<String>,
+}
+
+impl<R: BufRead> ProtocolReader<R> {
+    pub fn new(reader: R) -> ProtocolReader<R> {
+        ProtocolReader { reader, line: 0, offset: 0, tokens: VecDeque::new() }
+    }
+    pub fn line(&self) -> usize {
+        self.line
+    }
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+    //read lines until a token is found, false at the end of input
+    fn fill(&mut self) -> Result<bool, ProtocolError> {
+        while self.tokens.is_empty() {
+            let mut buf = Vec::new();
+            let size = self.reader.read_until(b'\n', &mut buf)?;
+            if size == 0 {
+                return Ok(false);
+            }
+            self.line += 1;
+            self.offset += size;
+            let text = String::from_utf8_lossy(&buf);
+            self.tokens = text.split_whitespace().map(|token| token.to_string()).collect();
+        }
+        Ok(true)
+    }
+    pub fn is_eof(&mut self) -> Result<bool, ProtocolError> {
+        Ok(!self.fill()?)
+    }
+    fn next_token(&mut self, expected: &'static str) -> Result<String, ProtocolError> {
+        if !self.fill()? {
+            return Err(ProtocolError::UnexpectedEof { line: self.line, expected });
+        }
+        Ok(self.tokens.pop_front().unwrap())
+    }
+    fn invalid(&self, token: String, expected: &'static str) -> ProtocolError {
+        ProtocolError::InvalidToken { line: self.line, token, expected }
+    }
+    pub fn read<T: std::str::FromStr>(&mut self, expected: &'static str) -> Result<T, ProtocolError> {
+        let token = self.next_token(expected)?;
+        token.parse().map_err(|_| self.invalid(token, expected))
+    }
+    //a number in min..=max
+    pub fn read_range(&mut self, min: u32, max: u32, expected: &'static str) -> Result<u32, ProtocolError> {
+        let token = self.next_token(expected)?;
+        match token.parse::<u32>() {
+            Ok(value) if min <= value && value <= max => Ok(value),
+            _ => Err(self.invalid(token, expected)),
+        }
+    }
+    pub fn read_end(&mut self) -> Result<(), ProtocolError> {
+        let token = self.next_token(END_TOKEN)?;
+        if token != END_TOKEN {
+            return Err(self.invalid(token, END_TOKEN));
+        }
+        Ok(())
+    }
+    //packs as given, before any rotation
+    pub fn read_raw_packs(&mut self) -> Result<Vec<Pack>, ProtocolError> {
+        (0..MAX_TURN)
+            .map(|_| {
+                let mut blocks = [0; 4];
+                for block in blocks.iter_mut() {
+                    *block = self.read_range(0, 9, "a pack block (0-9)")? as u8;
+                }
+                self.read_end()?;
+                Ok(Pack::new(&blocks))
+            })
+            .collect()
+    }
+    pub fn read_packs(&mut self) -> Result<Vec<Vec<(Pack, usize)>>, ProtocolError> {
+        Ok(self
+            .read_raw_packs()?
+            .iter()
+            .map(|pack| pack.unique_rotations())
+            .collect())
+    }
+    //the turn number at the start of a turn's input, None at the end of input
+    pub fn read_turn(&mut self) -> Result<Option<usize>, ProtocolError> {
+        if self.is_eof()? {
+            return Ok(None);
+        }
+        let turn = self.read_range(0, MAX_TURN as u32 - 1, "a turn (0-499)")?;
+        Ok(Some(turn as usize))
+    }
+    //the game never sends floating blocks but a hand-written record might, they are dropped
+    pub fn read_game_status(&mut self) -> Result<GameStatus, ProtocolError> {
+        Ok(self.read_game_status_with_stability()?.0)
+    }
+    //the game status and what was wrong with its board, for callers that report it
+    pub fn read_game_status_with_stability(&mut self) -> Result<(GameStatus, InputStability), ProtocolError> {
+        let rest_time_milliseconds: u32 = self.read("rest time in milliseconds")?;
+        let obstacle_block_count: u32 = self.read("an obstacle block count")?;
+        let skill_point = self.read_range(0, MAX_SKILL_POINT, "a skill point (0-100)")?;
+        let cumulative_game_score: u32 = self.read("a cumulative game score")?;
+
+        let mut input_board = [[0; FIELD_WIDTH]; INPUT_FIELD_HEIGHT];
+        for row in input_board.iter_mut() {
+            for block in row.iter_mut() {
+                let value = self.read_range(0, OBSTACLE_BLOCK as u32, "a board block (0-9 or 11)")?;
+                if value == 10 {
+                    return Err(self.invalid(value.to_string(), "a board block (0-9 or 11)"));
+                }
+                *block = value as u8;
+            }
+        }
+        self.read_end()?;
+        let (board, stability) = Board::new_with_gravity(input_board, false);
+        let game_status = GameStatus::default()
+            .with_rest_time_milliseconds(rest_time_milliseconds)
+            .with_obstacle_block_count(obstacle_block_count)
+            .with_skill_point(skill_point)
+            .with_cumulative_game_score(cumulative_game_score)
+            .with_board(board);
+        Ok((game_status, stability))
+    }
+}
+
+#[cfg(test)]
+fn game_status_text(board_line: &str) -> String {
+    let mut text = String::from("180000\n0\n0\n0\n");
+    for _ in 0..INPUT_FIELD_HEIGHT - 1 {
+        text.push_str("0 0 0 0 0 0 0 0 0 0\n");
+    }
+    text.push_str(board_line);
+    text.push_str("\nEND\n");
+    text
+}
+
+#[test]
+fn test_read_game_status() {
+    let text = format!("3\n{}{}", game_status_text("0 0 0 0 0 0 0 0 11 4"), game_status_text("1 0 0 0 0 0 0 0 0 0"));
+    let mut reader = ProtocolReader::new(text.as_bytes());
+    assert_eq!(reader.read_turn().unwrap(), Some(3));
+    let player = reader.read_game_status().unwrap();
+    let enemy = reader.read_game_status().unwrap();
+    assert_eq!(player.board().get(0, 8), OBSTACLE_BLOCK);
+    assert_eq!(enemy.board().heights[0], 1);
+    assert_eq!(reader.offset(), text.len());
+    //clean end of input
+    assert_eq!(reader.read_turn().unwrap(), None);
+
+    //a floating block falls
+    let mut text = game_status_text("0 0 0 0 0 0 0 0 0 0");
+    text = text.replacen("0 0 0 0 0 0 0 0 0 0", "3 0 0 0 0 0 0 0 0 0", 1);
+    let (status, stability) = ProtocolReader::new(text.as_bytes()).read_game_status_with_stability().unwrap();
+    assert_eq!(status.board().get(0, 0), 3);
+    assert_eq!(stability, InputStability { fallen_block_count: 1, pending_chain_count: 0 });
+    let (_, stability) = ProtocolReader::new(game_status_text("1 0 0 0 0 0 0 0 0 0").as_bytes())
+        .read_game_status_with_stability()
+        .unwrap();
+    assert!(stability.is_stable());
+}
+
+#[test]
+fn test_protocol_error() {
+    let text = game_status_text("0 0 0 0 0 0 0 0 10 4");
+    let e = ProtocolReader::new(text.as_bytes()).read_game_status().unwrap_err();
+    assert_eq!(e.to_string(), "line 20: expected a board block (0-9 or 11), found \"10\"");
+
+    let text = game_status_text("0 0 0 0 0 0 0 0 0 4").replace("END", "ENX");
+    let e = ProtocolReader::new(text.as_bytes()).read_game_status().unwrap_err();
+    assert_eq!(e.to_string(), "line 21: expected END, found \"ENX\"");
+
+    let text = "180000\n0\n101\n";
+    let e = ProtocolReader::new(text.as_bytes()).read_game_status().unwrap_err();
+    assert_eq!(e.to_string(), "line 3: expected a skill point (0-100), found \"101\"");
+
+    let text = "1 2 3 4\nEND\n5 6 x 8\n";
+    let e = ProtocolReader::new(text.as_bytes()).read_raw_packs().unwrap_err();
+    assert_eq!(e.to_string(), "line 3: expected a pack block (0-9), found \"x\"");
+
+    //input ending in the middle of a turn is an error
+    let text = "12\n180000\n";
+    let mut reader = ProtocolReader::new(text.as_bytes());
+    assert_eq!(reader.read_turn().unwrap(), Some(12));
+    let e = reader.read_game_status().unwrap_err();
+    assert_eq!(e.to_string(), "line 2: expected an obstacle block count, found end of input");
+}
@@ -0,0 +1,283 @@
+use crate::board::{FIELD_HEIGHT, FIELD_WIDTH};
+use crate::command::Command;
+use crate::game_status::GameStatus;
+use crate::pack::Pack;
+use crate::simulator;
+use crate::simulator::Simulator;
+use crate::solver::MAX_TURN;
+
+pub const MAX_SKILL_POINT: u32 = 100;
+pub const SPELL_SKILL_POINT: u32 = 80;
+//skill point for a drop which erases blocks
+pub const CHAIN_SKILL_POINT: u32 = 8;
+//a chain of this count reduces the opponent's skill point by 12 + 2 * chain count
+pub const SKILL_REDUCE_CHAIN_COUNT: u8 = 3;
+pub const INITIAL_REST_TIME_MILLISECONDS: u32 = 180000;
+
+//skill point a chain takes from the opponent
+pub fn calculate_skill_reduce(chain_count: u8) -> u32 {
+    if chain_count >= SKILL_REDUCE_CHAIN_COUNT {
+        12 + 2 * chain_count as u32
+    } else {
+        0
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GameResult {
+    //index of the winner
+    Win(usize),
+    Draw,
+}
+
+//what a command did in a turn
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ActionOutcome {
+    pub chain_count: u8,
+    //blocks erased by a spell
+    pub erased_block_count: u32,
+    pub gain_game_score: u32,
+    pub obstacle_block_count: u32,
+    //obstacles left after offsetting own obstacles
+    pub sent_obstacle_block_count: u32,
+    pub game_over: bool,
+}
+
+impl ActionOutcome {
+    pub fn default() -> ActionOutcome {
+        ActionOutcome {
+            chain_count: 0,
+            erased_block_count: 0,
+            gain_game_score: 0,
+            obstacle_block_count: 0,
+            sent_obstacle_block_count: 0,
+            game_over: false,
+        }
+    }
+}
+
+//plays both players' commands by the game rules
+#[derive(Debug, Clone)]
+pub struct Referee {
+    packs: Vec<Pack>,
+    turn: usize,
+    players: [GameStatus; 2],
+    result: Option<GameResult>,
+}
+
+impl Referee {
+    pub fn new(packs: Vec<Pack>) -> Referee {
+        let status = GameStatus::default().with_rest_time_milliseconds(INITIAL_REST_TIME_MILLISECONDS);
+        Referee {
+            packs,
+            turn: 0,
+            players: [status.clone(), status],
+            result: None,
+        }
+    }
+    pub fn with_turn(mut self, turn: usize) -> Self {
+        self.turn = turn;
+        self
+    }
+    pub fn with_players(mut self, player: GameStatus, enemy: GameStatus) -> Self {
+        self.players = [player, enemy];
+        self
+    }
+    pub fn turn(&self) -> usize {
+        self.turn
+    }
+    pub fn packs(&self) -> &Vec<Pack> {
+        &self.packs
+    }
+    pub fn pack(&self) -> Pack {
+        self.packs[self.turn]
+    }
+    pub fn player(&self, idx: usize) -> &GameStatus {
+        &self.players[idx]
+    }
+    pub fn result(&self) -> Option<GameResult> {
+        self.result
+    }
+    pub fn is_valid(&self, idx: usize, command: Command) -> bool {
+        match command {
+            Command::Drop((point, rotate_count)) => point <= 8 && rotate_count <= 3,
+            Command::Spell => self.players[idx].skill_point() >= SPELL_SKILL_POINT,
+        }
+    }
+    pub fn step(&mut self, simulator: &mut Simulator, commands: [Command; 2]) -> [ActionOutcome; 2] {
+        debug_assert!(self.result.is_none());
+        debug_assert!(self.turn < MAX_TURN);
+        let pack = self.pack();
+        let mut outcomes = [ActionOutcome::default(); 2];
+        let mut skill_points = [0; 2];
+        let mut obstacle_block_counts = [0; 2];
+        let mut boards = [self.players[0].board(), self.players[1].board()];
+        for idx in 0..2 {
+            let status = &self.players[idx];
+            let board = &mut boards[idx];
+            let outcome = &mut outcomes[idx];
+            obstacle_block_counts[idx] = status.obstacle_block_count();
+            skill_points[idx] = status.skill_point();
+            if obstacle_block_counts[idx] >= FIELD_WIDTH as u32 {
+                board.drop_obstacles();
+                board.debug_check_invariants();
+                obstacle_block_counts[idx] -= FIELD_WIDTH as u32;
+            }
+            debug_assert!(self.is_valid(idx, commands[idx]));
+            match commands[idx] {
+                Command::Drop((point, rotate_count)) => {
+                    let pack = pack.rotated(rotate_count);
+                    //no room to drop the pack
+                    if (point..point + 2).any(|x| board.heights[x] + 2 > FIELD_HEIGHT) {
+                        outcome.game_over = true;
+                        continue;
+                    }
+                    outcome.chain_count = simulator.simulate(board, point, &pack);
+                    outcome.gain_game_score = simulator::calculate_game_score(outcome.chain_count);
+                    outcome.obstacle_block_count =
+                        simulator::calculate_obstacle_count(outcome.gain_game_score, 0);
+                    if outcome.chain_count > 0 {
+                        skill_points[idx] =
+                            std::cmp::min(MAX_SKILL_POINT, skill_points[idx] + CHAIN_SKILL_POINT);
+                    }
+                }
+                Command::Spell => {
+                    let (erased_block_count, chain_count) = simulator.spell(board);
+                    let skill_score = if erased_block_count > 0 {
+                        simulator::calculate_skill_score(erased_block_count)
+                    } else {
+                        0
+                    };
+                    let chain_score = simulator::calculate_game_score(chain_count);
+                    outcome.erased_block_count = erased_block_count;
+                    outcome.chain_count = chain_count;
+                    outcome.gain_game_score = skill_score + chain_score;
+                    outcome.obstacle_block_count =
+                        simulator::calculate_obstacle_count(chain_score, skill_score);
+                    skill_points[idx] = 0;
+                }
+            }
+            board.debug_check_invariants();
+            outcome.game_over = board.is_game_over();
+        }
+        for idx in 0..2 {
+            let enemy = &outcomes[1 - idx];
+            if commands[1 - idx] != Command::Spell {
+                skill_points[idx] -= std::cmp::min(skill_points[idx], calculate_skill_reduce(enemy.chain_count));
+            }
+            //offset own obstacles first
+            let outcome = &mut outcomes[idx];
+            if outcome.obstacle_block_count <= obstacle_block_counts[idx] {
+                obstacle_block_counts[idx] -= outcome.obstacle_block_count;
+            } else {
+                outcome.sent_obstacle_block_count =
+                    outcome.obstacle_block_count - obstacle_block_counts[idx];
+                obstacle_block_counts[idx] = 0;
+            }
+        }
+        for idx in 0..2 {
+            let status = &self.players[idx];
+            self.players[idx] = GameStatus::default()
+                .with_rest_time_milliseconds(status.rest_time_milliseconds())
+                .with_obstacle_block_count(
+                    obstacle_block_counts[idx] + outcomes[1 - idx].sent_obstacle_block_count,
+                )
+                .with_skill_point(skill_points[idx])
+                .with_cumulative_game_score(
+                    status.cumulative_game_score() + outcomes[idx].gain_game_score,
+                )
+                .with_board(boards[idx]);
+        }
+        self.turn += 1;
+        self.result = match (outcomes[0].game_over, outcomes[1].game_over) {
+            (true, true) => Some(GameResult::Draw),
+            (true, false) => Some(GameResult::Win(1)),
+            (false, true) => Some(GameResult::Win(0)),
+            (false, false) if self.turn >= std::cmp::min(MAX_TURN, self.packs.len()) => {
+                Some(GameResult::Draw)
+            }
+            _ => None,
+        };
+        outcomes
+    }
+}
+
+#[cfg(test)]
+fn test_packs() -> Vec<Pack> {
+    vec![Pack::new(&[0, 0, 9, 4]); MAX_TURN]
+}
+
+#[test]
+fn test_step_obstacles() {
+    let mut referee = Referee::new(test_packs()).with_players(
+        GameStatus::default().with_obstacle_block_count(2).with_skill_point(10),
+        GameStatus::default().with_obstacle_block_count(13).with_skill_point(30),
+    );
+    let mut simulator = Simulator::new();
+    let outcomes = referee.step(&mut simulator, [Command::Drop((0, 0)), Command::Drop((0, 0))]);
+    assert_eq!(outcomes[0].chain_count, 0);
+    assert_eq!(outcomes[1].chain_count, 0);
+    //the enemy's first line dropped
+    assert_eq!(referee.player(1).obstacle_block_count(), 3);
+    assert_eq!(referee.player(1).board().heights, [2, 2, 1, 1, 1, 1, 1, 1, 1, 1]);
+    assert_eq!(referee.player(0).obstacle_block_count(), 2);
+    assert_eq!(referee.player(0).skill_point(), 10);
+    assert_eq!(referee.turn(), 1);
+    assert_eq!(referee.result(), None);
+}
+
+#[test]
+fn test_step_chain_and_spell() {
+    let board: crate::board::Board = "
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        1....5....
+    "
+    .parse()
+    .unwrap();
+    let mut referee = Referee::new(test_packs()).with_players(
+        GameStatus::default().with_board(board).with_obstacle_block_count(4).with_skill_point(10),
+        GameStatus::default().with_board(board).with_skill_point(80),
+    );
+    assert!(!referee.is_valid(0, Command::Spell));
+    assert!(referee.is_valid(1, Command::Spell));
+    let mut simulator = Simulator::new();
+    //9 lands on 1
+    let outcomes = referee.step(&mut simulator, [Command::Drop((0, 0)), Command::Spell]);
+    assert_eq!(outcomes[0].chain_count, 1);
+    assert_eq!(outcomes[0].gain_game_score, 1);
+    assert_eq!(outcomes[0].sent_obstacle_block_count, 0);
+    assert_eq!(referee.player(0).skill_point(), 18);
+    assert_eq!(referee.player(0).cumulative_game_score(), 1);
+    //a lone 5 explodes
+    assert_eq!(outcomes[1].erased_block_count, 1);
+    assert_eq!(outcomes[1].gain_game_score, 26);
+    assert_eq!(outcomes[1].obstacle_block_count, 13);
+    assert_eq!(outcomes[1].sent_obstacle_block_count, 13);
+    assert_eq!(referee.player(0).obstacle_block_count(), 4 + 13);
+    assert_eq!(referee.player(1).skill_point(), 0);
+}
+
+#[test]
+fn test_step_game_over() {
+    let rows = vec!["#........."; crate::board::INPUT_FIELD_HEIGHT];
+    let board: crate::board::Board = rows.join("\n").parse().unwrap();
+    let mut referee = Referee::new(test_packs())
+        .with_players(GameStatus::default().with_board(board), GameStatus::default());
+    let mut simulator = Simulator::new();
+    referee.step(&mut simulator, [Command::Drop((0, 0)), Command::Drop((4, 0))]);
+    assert_eq!(referee.result(), Some(GameResult::Win(1)));
+}
@@ -0,0 +1,325 @@
+//A deliberately naive simulator to test Simulator against.
+//It works on a plain 2-D array and scans the whole board at every step.
+use crate::bit_plane_board::BitPlaneBoard;
+use crate::board::{Board, EMPTY_BLOCK, ERASING_SUM, FIELD_HEIGHT, FIELD_WIDTH, INPUT_FIELD_HEIGHT, OBSTACLE_BLOCK};
+use crate::pack::Pack;
+use crate::simulator::SPELL_BLOCK;
+use crate::xorshift::Xorshift;
+
+//grid[y][x], y = 0 is the bottom
+pub type Grid = [[u8; FIELD_WIDTH]; FIELD_HEIGHT];
+
+pub fn from_board(board: &Board) -> Grid {
+    let mut grid = [[EMPTY_BLOCK; FIELD_WIDTH]; FIELD_HEIGHT];
+    for (y, row) in grid.iter_mut().enumerate() {
+        for (x, block) in row.iter_mut().enumerate() {
+            *block = board.get(y, x);
+        }
+    }
+    grid
+}
+
+//only for grids without blocks in the hidden rows
+pub fn to_board(grid: &Grid) -> Board {
+    let mut input_board = [[EMPTY_BLOCK; FIELD_WIDTH]; INPUT_FIELD_HEIGHT];
+    for y in 0..FIELD_HEIGHT {
+        for x in 0..FIELD_WIDTH {
+            if y < INPUT_FIELD_HEIGHT {
+                input_board[INPUT_FIELD_HEIGHT - 1 - y][x] = grid[y][x];
+            } else {
+                assert_eq!(grid[y][x], EMPTY_BLOCK);
+            }
+        }
+    }
+    Board::new(input_board)
+}
+
+//one above the highest block
+pub fn heights(grid: &Grid) -> [usize; FIELD_WIDTH] {
+    let mut heights = [0; FIELD_WIDTH];
+    for (x, height) in heights.iter_mut().enumerate() {
+        for (y, row) in grid.iter().enumerate() {
+            if row[x] != EMPTY_BLOCK {
+                *height = y + 1;
+            }
+        }
+    }
+    heights
+}
+
+pub fn apply_gravity(grid: &mut Grid) {
+    for x in 0..FIELD_WIDTH {
+        let blocks: Vec<u8> = grid.iter().map(|row| row[x]).filter(|&block| block != EMPTY_BLOCK).collect();
+        for (y, row) in grid.iter_mut().enumerate() {
+            row[x] = blocks.get(y).cloned().unwrap_or(EMPTY_BLOCK);
+        }
+    }
+}
+
+fn is_number(block: u8) -> bool {
+    block != EMPTY_BLOCK && block != OBSTACLE_BLOCK
+}
+
+fn neighbors(y: usize, x: usize) -> Vec<(usize, usize)> {
+    let mut res = Vec::new();
+    for dy in -1i32..=1 {
+        for dx in -1i32..=1 {
+            let (ny, nx) = (y as i32 + dy, x as i32 + dx);
+            if (dy, dx) == (0, 0) || ny < 0 || nx < 0 {
+                continue;
+            }
+            if ny as usize >= FIELD_HEIGHT || nx as usize >= FIELD_WIDTH {
+                continue;
+            }
+            res.push((ny as usize, nx as usize));
+        }
+    }
+    res
+}
+
+//every pair of neighboring numbers which sums up to ERASING_SUM
+pub fn erase_blocks(grid: &Grid) -> Vec<(usize, usize)> {
+    let mut res = Vec::new();
+    for y in 0..FIELD_HEIGHT {
+        for x in 0..FIELD_WIDTH {
+            if !is_number(grid[y][x]) {
+                continue;
+            }
+            let erased = neighbors(y, x).into_iter().any(|(ny, nx)| {
+                is_number(grid[ny][nx]) && grid[y][x] + grid[ny][nx] == ERASING_SUM
+            });
+            if erased {
+                res.push((y, x));
+            }
+        }
+    }
+    res
+}
+
+pub fn resolve_chains(grid: &mut Grid) -> u8 {
+    let mut chain_count = 0;
+    loop {
+        apply_gravity(grid);
+        let blocks = erase_blocks(grid);
+        if blocks.is_empty() {
+            return chain_count;
+        }
+        for (y, x) in blocks {
+            grid[y][x] = EMPTY_BLOCK;
+        }
+        chain_count += 1;
+    }
+}
+
+//put the pack on the top rows and let it fall, None if the pack doesn't fit
+pub fn simulate(grid: &mut Grid, point: usize, pack: &Pack) -> Option<u8> {
+    for idx in 0..4 {
+        let (y, x) = (FIELD_HEIGHT - 1 - idx / 2, point + idx % 2);
+        if pack.get(idx) == EMPTY_BLOCK {
+            continue;
+        }
+        if grid[y][x] != EMPTY_BLOCK {
+            return None;
+        }
+        grid[y][x] = pack.get(idx);
+    }
+    Some(resolve_chains(grid))
+}
+
+pub fn drop_obstacles(grid: &mut Grid) {
+    for block in grid[FIELD_HEIGHT - 1].iter_mut() {
+        assert_eq!(*block, EMPTY_BLOCK);
+        *block = OBSTACLE_BLOCK;
+    }
+    apply_gravity(grid);
+}
+
+//(erased block count, chain count)
+pub fn spell(grid: &mut Grid) -> (u32, u8) {
+    let mut erased = [[false; FIELD_WIDTH]; FIELD_HEIGHT];
+    for y in 0..FIELD_HEIGHT {
+        for x in 0..FIELD_WIDTH {
+            if grid[y][x] != SPELL_BLOCK {
+                continue;
+            }
+            erased[y][x] = true;
+            for (ny, nx) in neighbors(y, x) {
+                if is_number(grid[ny][nx]) {
+                    erased[ny][nx] = true;
+                }
+            }
+        }
+    }
+    let mut erased_block_count = 0;
+    for y in 0..FIELD_HEIGHT {
+        for x in 0..FIELD_WIDTH {
+            if erased[y][x] {
+                grid[y][x] = EMPTY_BLOCK;
+                erased_block_count += 1;
+            }
+        }
+    }
+    if erased_block_count == 0 {
+        return (0, 0);
+    }
+    (erased_block_count, resolve_chains(grid))
+}
+
+//a board without anything to erase, columns up to max_height
+pub fn random_stable_grid(rnd: &mut Xorshift, max_height: usize) -> Grid {
+    let mut grid = [[EMPTY_BLOCK; FIELD_WIDTH]; FIELD_HEIGHT];
+    for x in 0..FIELD_WIDTH {
+        let height = rnd.rand(max_height as u64 + 1) as usize;
+        for row in grid.iter_mut().take(height) {
+            row[x] = if rnd.rand(8) == 0 { OBSTACLE_BLOCK } else { rnd.rand(9) as u8 + 1 };
+        }
+    }
+    resolve_chains(&mut grid);
+    grid
+}
+
+pub fn random_pack(rnd: &mut Xorshift) -> Pack {
+    let mut blocks = [0; 4];
+    for block in blocks.iter_mut() {
+        *block = rnd.rand(10) as u8;
+    }
+    Pack::new(&blocks)
+}
+
+//compare Simulator with the reference on random boards, Err describes the first mismatch
+pub fn differential_test(seed: u64, iterations: usize) -> Result<(), String> {
+    let mut rnd = Xorshift::with_seed(seed);
+    let mut simulator = crate::simulator::Simulator::new();
+    for iteration in 0..iterations {
+        let grid = random_stable_grid(&mut rnd, INPUT_FIELD_HEIGHT);
+        let board = to_board(&grid);
+        let compare = |name: &str, expected: &Grid, actual: &Board| -> Result<(), String> {
+            if let Err(e) = actual.check_invariants() {
+                return Err(format!("iteration {}: {} broke the board: {}\n{:#}", iteration, name, e, actual));
+            }
+            if from_board(actual) != *expected || actual.heights != heights(expected) {
+                return Err(format!(
+                    "iteration {}: {} differs\ninitial\n{}\nexpected\n{}\nactual\n{:#}",
+                    iteration,
+                    name,
+                    board,
+                    to_board_unchecked(expected),
+                    actual
+                ));
+            }
+            Ok(())
+        };
+
+        let pack = random_pack(&mut rnd);
+        let rotate_count = rnd.rand(4) as usize;
+        let point = rnd.rand(9) as usize;
+        let rotated = pack.rotated(rotate_count);
+        if (point..point + 2).all(|x| board.heights[x] + 2 <= FIELD_HEIGHT) {
+            let mut expected = grid;
+            let expected_chain_count = simulate(&mut expected, point, &rotated).unwrap();
+            let mut actual = board;
+            let chain_count = simulator.simulate(&mut actual, point, &rotated);
+            if chain_count != expected_chain_count {
+                return Err(format!(
+                    "iteration {}: chain count {} != {} dropping {:?} at {}\n{}",
+                    iteration, chain_count, expected_chain_count, rotated, point, board
+                ));
+            }
+            compare("simulate", &expected, &actual)?;
+
+            let mut bit_plane_board = BitPlaneBoard::from_board(&board);
+            if bit_plane_board.simulate(point, &rotated) != expected_chain_count {
+                return Err(format!(
+                    "iteration {}: bit plane chain count differs dropping {:?} at {}\n{}",
+                    iteration, rotated, point, board
+                ));
+            }
+            compare("bit plane simulate", &expected, &bit_plane_board.to_board())?;
+        }
+
+        let mut expected = grid;
+        drop_obstacles(&mut expected);
+        let mut actual = board;
+        actual.drop_obstacles();
+        compare("drop_obstacles", &expected, &actual)?;
+
+        let mut expected = grid;
+        let expected_spell = spell(&mut expected);
+        let mut actual = board;
+        let actual_spell = simulator.spell(&mut actual);
+        if actual_spell != expected_spell {
+            return Err(format!(
+                "iteration {}: spell {:?} != {:?}\n{}",
+                iteration, actual_spell, expected_spell, board
+            ));
+        }
+        compare("spell", &expected, &actual)?;
+
+        let mut bit_plane_board = BitPlaneBoard::from_board(&board);
+        if bit_plane_board.spell() != expected_spell {
+            return Err(format!("iteration {}: bit plane spell differs\n{}", iteration, board));
+        }
+        compare("bit plane spell", &expected, &bit_plane_board.to_board())?;
+
+        let mut bit_plane_board = BitPlaneBoard::from_board(&board);
+        bit_plane_board.drop_obstacles();
+        let mut expected = grid;
+        drop_obstacles(&mut expected);
+        compare("bit plane drop_obstacles", &expected, &bit_plane_board.to_board())?;
+    }
+    Ok(())
+}
+
+//for error messages, shows the hidden rows too
+fn to_board_unchecked(grid: &Grid) -> String {
+    (0..FIELD_HEIGHT)
+        .rev()
+        .map(|y| grid[y].iter().map(|&block| crate::board::block_char(block)).collect::<String>())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[test]
+fn test_reference_simulate() {
+    let board: Board = "
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        .....3....
+        .....2....
+        .....1....
+    "
+    .parse()
+    .unwrap();
+    let mut grid = from_board(&board);
+    //9 erases with 1 and 7 with 3 at once
+    assert_eq!(simulate(&mut grid, 4, &Pack::new(&[7, 0, 9, 0])), Some(1));
+    assert_eq!(heights(&grid), [0, 0, 0, 0, 0, 1, 0, 0, 0, 0]);
+    assert_eq!(grid[0][5], 2);
+}
+
+#[test]
+fn test_differential() {
+    //cargo test --release test_differential_million -- --ignored for the long run
+    if let Err(e) = differential_test(1, 1000) {
+        panic!("{}", e);
+    }
+}
+
+#[test]
+#[ignore]
+fn test_differential_million() {
+    if let Err(e) = differential_test(2, 1_000_000) {
+        panic!("{}", e);
+    }
+}
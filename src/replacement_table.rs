@@ -0,0 +1,224 @@
+//A fixed-size hash table for memoizing by zobrist hash. Keys are spread over buckets of a few
+//ways, a full bucket evicts a way of an older generation first, then one which wasn't hit since
+//the last time the clock passed it.
+use crate::zobrist_hash_table::ZobristHash;
+
+const WAY_COUNT: usize = 4;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub len: usize,
+    pub capacity: usize,
+}
+
+impl CacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        if self.hits + self.misses == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / (self.hits + self.misses) as f64
+    }
+}
+
+impl std::ops::Add for CacheStats {
+    type Output = CacheStats;
+    fn add(self, other: CacheStats) -> CacheStats {
+        CacheStats {
+            hits: self.hits + other.hits,
+            misses: self.misses + other.misses,
+            evictions: self.evictions + other.evictions,
+            len: self.len + other.len,
+            capacity: self.capacity + other.capacity,
+        }
+    }
+}
+
+impl std::fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "entries {}/{} hits {} misses {} ({:.1}% hit) evictions {}",
+            self.len,
+            self.capacity,
+            self.hits,
+            self.misses,
+            100.0 * self.hit_rate(),
+            self.evictions
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Entry<V: Copy> {
+    key: ZobristHash,
+    value: V,
+    occupied: bool,
+    //second chance for the clock
+    referenced: bool,
+    //the generation of the last insert or hit, it wraps around so very old entries look new again
+    generation: u8,
+}
+
+pub struct ReplacementTable<V: Copy + Default> {
+    //allocated on the first insert
+    entries: Vec<Entry<V>>,
+    bucket_count: usize,
+    generation: u8,
+    len: usize,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl<V: Copy + Default> ReplacementTable<V> {
+    pub fn entry_size() -> usize {
+        std::mem::size_of::<Entry<V>>()
+    }
+    //as many buckets as fit in bytes, a power of two and at least one
+    pub fn with_capacity_bytes(bytes: usize) -> ReplacementTable<V> {
+        let buckets = std::cmp::max(1, bytes / (ReplacementTable::<V>::entry_size() * WAY_COUNT));
+        let bucket_count = if buckets.is_power_of_two() { buckets } else { buckets.next_power_of_two() / 2 };
+        ReplacementTable { entries: Vec::new(), bucket_count, generation: 0, len: 0, hits: 0, misses: 0, evictions: 0 }
+    }
+    pub fn capacity(&self) -> usize {
+        self.bucket_count * WAY_COUNT
+    }
+    pub fn capacity_bytes(&self) -> usize {
+        self.capacity() * ReplacementTable::<V>::entry_size()
+    }
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub fn generation(&self) -> u8 {
+        self.generation
+    }
+    //entries of older generations are kept but replaced first
+    pub fn new_generation(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            evictions: self.evictions,
+            len: self.len,
+            capacity: self.capacity(),
+        }
+    }
+    #[inline]
+    fn bucket(&self, key: ZobristHash) -> std::ops::Range<usize> {
+        //the low bits pick the bucket
+        let start = (key as usize & (self.bucket_count - 1)) * WAY_COUNT;
+        start..start + WAY_COUNT
+    }
+    pub fn get(&mut self, key: ZobristHash) -> Option<V> {
+        let bucket = self.bucket(key);
+        if let Some(entries) = self.entries.get_mut(bucket) {
+            for entry in entries.iter_mut() {
+                if entry.occupied && entry.key == key {
+                    entry.referenced = true;
+                    entry.generation = self.generation;
+                    self.hits += 1;
+                    return Some(entry.value);
+                }
+            }
+        }
+        self.misses += 1;
+        None
+    }
+    pub fn insert(&mut self, key: ZobristHash, value: V) {
+        if self.entries.is_empty() {
+            let empty = Entry { key: 0, value: V::default(), occupied: false, referenced: false, generation: 0 };
+            self.entries = vec![empty; self.capacity()];
+        }
+        let bucket = self.bucket(key);
+        let generation = self.generation;
+        let entries = &mut self.entries[bucket];
+        let new_entry = Entry { key, value, occupied: true, referenced: false, generation };
+        //overwrite the same key or take an empty way
+        if let Some(entry) = entries.iter_mut().find(|entry| !entry.occupied || entry.key == key) {
+            if !entry.occupied {
+                self.len += 1;
+            }
+            *entry = new_entry;
+            return;
+        }
+        let victim = match entries.iter().position(|entry| entry.generation != generation) {
+            Some(way) => way,
+            None => {
+                //clear the second chances on the way, the first way if every way had one
+                let mut victim = 0;
+                for (way, entry) in entries.iter_mut().enumerate() {
+                    if !entry.referenced {
+                        victim = way;
+                        break;
+                    }
+                    entry.referenced = false;
+                }
+                victim
+            }
+        };
+        entries[victim] = new_entry;
+        self.evictions += 1;
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (ZobristHash, V)> + '_ {
+        self.entries.iter().filter(|entry| entry.occupied).map(|entry| (entry.key, entry.value))
+    }
+    //keeps the allocation and the counters
+    pub fn clear(&mut self) {
+        for entry in self.entries.iter_mut() {
+            entry.occupied = false;
+        }
+        self.len = 0;
+    }
+}
+
+#[test]
+fn test_replacement_table() {
+    let mut table: ReplacementTable<(u8, u8)> = ReplacementTable::with_capacity_bytes(1000);
+    assert!(table.capacity_bytes() <= 1000);
+    assert!(table.capacity().is_power_of_two());
+    assert_eq!(table.get(3), None);
+    table.insert(3, (1, 2));
+    table.insert(3, (2, 3));
+    assert_eq!(table.get(3), Some((2, 3)));
+    assert_eq!(table.len(), 1);
+
+    //keys of one bucket, the hit one survives
+    let stride = (table.capacity() / WAY_COUNT) as u64;
+    let keys: Vec<ZobristHash> = (0..WAY_COUNT as u64).map(|i| 5 + i * stride).collect();
+    for &key in keys.iter() {
+        table.insert(key, (key as u8, 0));
+    }
+    assert_eq!(table.get(keys[0]), Some((keys[0] as u8, 0)));
+    table.insert(5 + 100 * stride, (7, 7));
+    assert_eq!(table.get(keys[0]), Some((keys[0] as u8, 0)));
+    assert_eq!(table.get(keys[1]), None);
+    assert_eq!(table.get(5 + 100 * stride), Some((7, 7)));
+    assert_eq!(table.len(), 1 + WAY_COUNT);
+
+    let stats = table.stats();
+    assert_eq!((stats.hits, stats.misses, stats.evictions), (4, 2, 1));
+    //ways untouched in the current generation go first
+    table.new_generation();
+    let key = 5 + 100 * stride;
+    assert_eq!(table.get(key), Some((7, 7)));
+    assert_eq!(table.get(keys[0]), Some((keys[0] as u8, 0)));
+    table.insert(5 + 200 * stride, (8, 8));
+    assert_eq!(table.get(keys[0]), Some((keys[0] as u8, 0)));
+    assert_eq!(table.get(key), Some((7, 7)));
+    assert_eq!(table.get(keys[2]), None);
+    assert_eq!(table.len(), 1 + WAY_COUNT);
+    assert_eq!(table.stats().evictions, 2);
+
+    table.clear();
+    assert!(table.is_empty());
+    assert_eq!(table.get(3), None);
+    assert_eq!(table.stats().evictions, 2);
+}
@@ -0,0 +1,105 @@
+use crate::command::Command;
+use crate::match_record::MatchRecord;
+use crate::search_result::{format_principal_variation, SearchResult};
+use crate::solver::Solver;
+use crate::solver_config::SolverConfig;
+
+//a turn where the replayed solver chose another command than the record
+#[derive(Debug, Clone)]
+pub struct DecisionDiff {
+    pub turn: usize,
+    pub recorded: Command,
+    pub replayed: Command,
+    //both commands evaluated by the replayed search, None if it never tried the command
+    pub recorded_score: Option<(f64, f64)>,
+    pub replayed_score: Option<(f64, f64)>,
+    pub recorded_principal_variation: Option<Vec<Command>>,
+    pub replayed_principal_variation: Vec<Command>,
+}
+
+impl std::fmt::Display for DecisionDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let score = |score: Option<(f64, f64)>| {
+            score.map_or("-".to_string(), |(a, b)| format!("({:.3}, {:.3})", a, b))
+        };
+        writeln!(f, "turn {}", self.turn)?;
+        write!(f, "  recorded {} score {}", self.recorded, score(self.recorded_score))?;
+        if let Some(pv) = self.recorded_principal_variation.as_ref() {
+            write!(f, " pv {}", format_principal_variation(pv))?;
+        }
+        writeln!(f)?;
+        write!(
+            f,
+            "  replayed {} score {} pv {}",
+            self.replayed,
+            score(self.replayed_score),
+            format_principal_variation(&self.replayed_principal_variation)
+        )
+    }
+}
+
+//feed every turn of a record into a fresh solver in order
+pub fn replay_record<F: FnMut(usize, &SearchResult)>(
+    record: &MatchRecord,
+    seed: u64,
+    config: SolverConfig,
+    mut on_turn: F,
+) -> Vec<DecisionDiff> {
+    let mut solver = Solver::default().with_seed(seed);
+    solver.set_config(config);
+    solver.set_packs(record.packs.clone());
+    let mut diffs = Vec::new();
+    for turn_record in record.turns.iter() {
+        solver.set_turn(turn_record.turn);
+        solver.set_game_status(turn_record.player.clone(), turn_record.enemy.clone());
+        let result = solver.think();
+        on_turn(turn_record.turn, &result);
+        let recorded = match turn_record.command {
+            Some(command) => command,
+            None => continue,
+        };
+        if recorded != result.command {
+            diffs.push(DecisionDiff {
+                turn: turn_record.turn,
+                recorded,
+                replayed: result.command,
+                recorded_score: result.command_score(recorded),
+                replayed_score: result.command_score(result.command),
+                recorded_principal_variation: turn_record.principal_variation(),
+                replayed_principal_variation: result.principal_variation.clone(),
+            });
+        }
+    }
+    diffs
+}
+
+#[test]
+fn test_replay_record() {
+    //little rest time keeps the beam small
+    let text = crate::match_record::sample_record_text()
+        .replace("180000", "5000")
+        .replace("179000", "5000")
+        .replace("179500", "5000");
+    let mut record = MatchRecord::parse(&text).unwrap();
+    let mut commands = Vec::new();
+    let diffs = replay_record(&record, 1024, SolverConfig::default(), |_, result| {
+        commands.push(result.command)
+    });
+    assert_eq!(commands.len(), 2);
+    //"8 0" and "0 1" in the record aren't what the solver plays
+    for diff in diffs.iter() {
+        let turn = diff.turn;
+        assert_eq!(diff.recorded, record.turns[turn].command.unwrap());
+        assert_eq!(diff.replayed, commands[turn]);
+        assert!(diff.replayed_score.is_some());
+        assert!(diff.recorded_score <= diff.replayed_score);
+        assert!(diff.to_string().starts_with(&format!("turn {}\n  recorded", turn)));
+    }
+
+    //the same seed reproduces the same decisions
+    for (turn_record, &command) in record.turns.iter_mut().zip(commands.iter()) {
+        turn_record.command = Some(command);
+    }
+    let diffs = replay_record(&record, 1024, SolverConfig::default(), |_, _| {});
+    assert!(diffs.is_empty());
+}
@@ -1,44 +0,0 @@
-//snippet from kenkoooo
-pub struct Scanner<R> {
-    pub stdin: R,
-}
-
-impl<R: std::io::Read> Scanner<R> {
-    pub fn read<T: std::str::FromStr>(&mut self) -> T {
-        use std::io::Read;
-        let buf = self.stdin
-            .by_ref()
-            .bytes()
-            .map(|b| b.unwrap())
-            .skip_while(|&b| b == b' ' || b == b'\n' || b == b'\r')
-            .take_while(|&b| b != b' ' && b != b'\n' && b != b'\r')
-            .collect::<Vec<_>>();
-        unsafe { std::str::from_utf8_unchecked(&buf) }
-            .parse()
-            .ok()
-            .expect("Parse error.")
-    }
-    #[allow(dead_code)]
-    pub fn read_line(&mut self) -> String {
-        use std::io::Read;
-        let buf = self.stdin
-            .by_ref()
-            .bytes()
-            .map(|b| b.unwrap())
-            .skip_while(|&b| b == b'\n' || b == b'\r')
-            .take_while(|&b| b != b'\n' && b != b'\r')
-            .collect::<Vec<_>>();
-        unsafe { std::str::from_utf8_unchecked(&buf) }
-            .parse()
-            .ok()
-            .expect("Parse error.")
-    }
-    #[allow(dead_code)]
-    pub fn vec<T: std::str::FromStr>(&mut self, n: usize) -> Vec<T> {
-        (0..n).map(|_| self.read()).collect()
-    }
-    #[allow(dead_code)]
-    pub fn chars(&mut self) -> Vec<char> {
-        self.read::<String>().chars().collect()
-    }
-}
\ No newline at end of file
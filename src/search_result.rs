@@ -15,14 +15,20 @@ pub struct SearchResult {
     pub gain_game_score: u32,
     pub search_depth: usize,
     pub board: Board,
+    //obstacles left to fall on the board after the plan, with the predicted attacks
+    pub pending_obstacle_block_count: u32,
     pub command: Command,
-    pub fire_right_now: bool
+    pub fire_right_now: bool,
+    //commands from this turn to the searched chain
+    pub principal_variation: Vec<Command>,
+    //the best search result score reached from each command of this turn
+    pub command_scores: Vec<(Command, (f64, f64))>,
 }
 
 
 impl SearchResult {
     pub fn default() -> SearchResult {
-        SearchResult { search_result_score: (0.0, 0.0), last_chain_count: 0, cumulative_game_score: 0, gain_game_score: 0, search_depth: 0, board: Board::default(), command: Command::default(), fire_right_now: false }
+        SearchResult { search_result_score: (0.0, 0.0), last_chain_count: 0, cumulative_game_score: 0, gain_game_score: 0, search_depth: 0, board: Board::default(), pending_obstacle_block_count: 0, command: Command::default(), fire_right_now: false, principal_variation: Vec::new(), command_scores: Vec::new() }
     }
     pub fn to_csv<T: std::io::Write>(&self, file: T) -> Result<(), Box<std::error::Error>> {
         let mut wtr = Writer::from_writer(file);
@@ -31,6 +37,20 @@ impl SearchResult {
         Ok(())
     }
 
+    pub fn command_score(&self, command: Command) -> Option<(f64, f64)> {
+        self.command_scores.iter().find(|&&(c, _)| c == command).map(|&(_, score)| score)
+    }
+    pub fn update_command_score(&mut self, command: Command, score: (f64, f64)) {
+        match self.command_scores.iter_mut().find(|(c, _)| *c == command) {
+            Some((_, best)) => {
+                if score > *best {
+                    *best = score;
+                }
+            }
+            None => self.command_scores.push((command, score)),
+        }
+    }
+
     pub fn log(&self) {
         eprintln!("search_score: {:?}", self.search_result_score);
         eprintln!("cumulative_game_score: {}", self.cumulative_game_score);
@@ -46,9 +66,45 @@ impl SearchResult {
             eprintln!();
         }*/
         eprintln!("Command: {:?}", self.command);
+        eprintln!("Principal variation: {}", format_principal_variation(&self.principal_variation));
     }
 }
 
+pub fn format_principal_variation(commands: &[Command]) -> String {
+    commands
+        .iter()
+        .map(|command| command.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+pub fn parse_principal_variation(s: &str) -> Result<Vec<Command>, String> {
+    s.split(',')
+        .filter(|command| !command.trim().is_empty())
+        .map(|command| command.parse())
+        .collect()
+}
+
+#[test]
+fn test_principal_variation() {
+    let commands = vec![Command::Drop((3, 1)), Command::Spell, Command::Drop((0, 0))];
+    let text = format_principal_variation(&commands);
+    assert_eq!(text, "3 1, S, 0 0");
+    assert_eq!(parse_principal_variation(&text), Ok(commands));
+    assert_eq!(parse_principal_variation(""), Ok(vec![]));
+    assert!(parse_principal_variation("3 1, 9 9").is_err());
+}
+
+#[test]
+fn test_command_score() {
+    let mut result = SearchResult::default();
+    result.update_command_score(Command::Drop((3, 1)), (1.0, 2.0));
+    result.update_command_score(Command::Drop((3, 1)), (0.0, 5.0));
+    result.update_command_score(Command::Drop((4, 0)), (0.0, 5.0));
+    assert_eq!(result.command_score(Command::Drop((3, 1))), Some((1.0, 2.0)));
+    assert_eq!(result.command_score(Command::Drop((4, 0))), Some((0.0, 5.0)));
+    assert_eq!(result.command_score(Command::Spell), None);
+}
 
 #[test]
 fn test_search_result_score_compare() {
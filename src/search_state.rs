@@ -1,4 +1,4 @@
-use crate::board::{Board, FIELD_WIDTH};
+use crate::board::{Board, CompactBoard, FIELD_WIDTH};
 use crate::command::Command;
 
 use crate::search_result::SearchResult;
@@ -7,23 +7,52 @@ use crate::zobrist_hash_table::{ZobristHash, ZOBRIST_HASH_TABLE_SCORE};
 use std::cmp::Ordering;
 use crate::pack::Pack;
 use std::hash::Hash;
-use crate::simulator::calculate_game_score;
+use crate::simulator::{calculate_game_score, calculate_obstacle_count_from_chain_count, Child};
+
+//the beam keeps thousands of these, keep it within SEARCH_STATE_SIZE
+pub const SEARCH_STATE_SIZE: usize = 152;
+
+//Option<Command> in a byte, point * 4 + rotate_count for drops
+const NO_COMMAND: u8 = 0xff;
+const SPELL_COMMAND: u8 = 0xfe;
+
+fn pack_command(command: Option<Command>) -> u8 {
+    match command {
+        None => NO_COMMAND,
+        Some(Command::Spell) => SPELL_COMMAND,
+        Some(Command::Drop((point, rotate_count))) => {
+            debug_assert!(point < 9 && rotate_count < 4);
+            (point * 4 + rotate_count) as u8
+        }
+    }
+}
+
+fn unpack_command(command: u8) -> Option<Command> {
+    match command {
+        NO_COMMAND => None,
+        SPELL_COMMAND => Some(Command::Spell),
+        _ => Some(Command::Drop(((command / 4) as usize, (command % 4) as usize))),
+    }
+}
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct SearchState {
-    board: Board,
+    board: CompactBoard,
     obstacle_block_count: u32,
     //obstacle block count what enemy spawned and attacked player
     spawn_obstacle_block_count: u32,
     //spawn obstacle block count what player spawned and attacked enemy
-    skill_point: u32,
+    skill_point: u8,
     cumulative_game_score: u32,
     //transition data
-    command: Option<Command>,
+    //see pack_command
+    command: u8,
     chain_count: u8,
-    point: usize,
+    point: u8,
     pack: Pack,
     search_score: f64,
+    //index of the move which made this state in the search
+    history: u32,
 }
 
 impl Eq for SearchState {}
@@ -44,16 +73,17 @@ impl Ord for SearchState {
 impl SearchState {
     pub fn default() -> SearchState {
         SearchState {
-            board: Board::default(),
+            board: CompactBoard::default(),
             obstacle_block_count: 0,
             spawn_obstacle_block_count: 0,
             skill_point: 0,
             cumulative_game_score: 0,
-            command: None,
+            command: NO_COMMAND,
             chain_count: 0,
             point: 0,
             pack: Pack::default(),
             search_score: 0.0,
+            history: 0,
         }
     }
     pub fn new(
@@ -69,24 +99,37 @@ impl SearchState {
         search_score: f64,
     ) -> SearchState {
         SearchState {
-            board,
+            board: CompactBoard::from_board(&board),
             obstacle_block_count,
             spawn_obstacle_block_count,
-            skill_point,
+            skill_point: skill_point as u8,
             cumulative_game_score,
-            command,
+            command: pack_command(command),
             chain_count,
-            point,
+            point: point as u8,
             pack,
             search_score,
+            history: 0,
         }
     }
 
+    //the state after a drop of the searching player, its chain offsets the pending obstacles first
+    pub fn after_drop(&self, child: &Child) -> SearchState {
+        child.board.debug_check_invariants();
+        let mut next_search_state = self
+            .with_board(child.board)
+            .with_cumulative_game_score(calculate_game_score(child.chain_count) + self.cumulative_game_score)
+            .with_spawn_obstacle_block_count(calculate_obstacle_count_from_chain_count(child.chain_count));
+        next_search_state.update_obstacle_block();
+        next_search_state
+    }
     pub fn update_obstacle_block_and_drop(&mut self) {
         self.update_obstacle_block();
         //Drop
         if self.obstacle_block_count >= FIELD_WIDTH as u32 {
-            self.board.drop_obstacles();
+            let mut board = self.board.to_board();
+            board.drop_obstacles();
+            self.board = CompactBoard::from_board(&board);
             self.obstacle_block_count -= FIELD_WIDTH as u32;
         }
     }
@@ -116,11 +159,23 @@ impl SearchState {
         self.spawn_obstacle_block_count += count;
         self
     }
+    #[inline]
     pub fn board(&self) -> Board {
+        self.board.to_board()
+    }
+    #[inline]
+    pub fn compact_board(&self) -> CompactBoard {
         self.board
     }
     pub fn with_board(mut self, board: Board) -> Self {
-        self.board = board;
+        self.board = CompactBoard::from_board(&board);
+        self
+    }
+    pub fn skill_point(&self) -> u32 {
+        self.skill_point as u32
+    }
+    pub fn with_skill_point(mut self, skill_point: u32) -> Self {
+        self.skill_point = skill_point as u8;
         self
     }
     pub fn cumulative_game_score(&self) -> u32 {
@@ -141,11 +196,11 @@ impl SearchState {
         self
     }
     pub fn set_command(&mut self, command: Command) {
-        self.command = Some(command);
+        self.command = pack_command(Some(command));
     }
     pub fn with_command(mut self, command: Command) -> Self {
-        debug_assert!(self.command.is_none());
-        self.command = Some(command);
+        debug_assert!(!self.is_command());
+        self.command = pack_command(Some(command));
         self
     }
     pub fn chain_count(&self) -> u8 {
@@ -162,10 +217,10 @@ impl SearchState {
         calculate_game_score(self.chain_count)
     }
     pub fn set_point(&mut self, point: usize) {
-        self.point = point;
+        self.point = point as u8;
     }
     pub fn with_point(mut self, point: usize) -> Self {
-        self.point = point;
+        self.point = point as u8;
         self
     }
     pub fn set_pack(&mut self, pack: Pack) {
@@ -175,11 +230,17 @@ impl SearchState {
         self.pack = pack;
         self
     }
+    pub fn history(&self) -> u32 {
+        self.history
+    }
+    pub fn set_history(&mut self, history: u32) {
+        self.history = history;
+    }
     pub fn is_command(&self) -> bool {
-        self.command.is_some()
+        self.command != NO_COMMAND
     }
     pub fn command(&self) -> Option<Command> {
-        self.command
+        unpack_command(self.command)
     }
     pub fn log(&self) {
         eprintln!("{:?}", *self);
@@ -309,7 +370,7 @@ fn test_update_obstacle_block_and_drop() {
         [0, 0, 11, 8, 1, 4, 8, 0, 0, 0],
         [11, 11, 1, 5, 1, 7, 7, 11, 11, 11],
     ];
-    assert_eq!(s.board, Board::new(board));
+    assert_eq!(s.board(), Board::new(board));
 }
 
 #[test]
@@ -333,6 +394,25 @@ fn test_update_obstacle_block() {
     assert_eq!(s.spawn_obstacle_block_count(), 0);
 }
 
+#[test]
+fn test_after_drop() {
+    let board = Board::default();
+    let child = |chain_count| Child { board, chain_count, point: 0, rotate_count: 0, pack: Pack::default() };
+    //obstacles sent before are kept, not added again
+    let s = SearchState::default().with_spawn_obstacle_block_count(12).with_cumulative_game_score(3);
+    let next = s.after_drop(&child(0));
+    assert_eq!(next.spawn_obstacle_block_count(), 12);
+    assert_eq!(next.cumulative_game_score(), 3);
+    let next = s.after_drop(&child(2));
+    assert_eq!(next.spawn_obstacle_block_count(), 12 + calculate_obstacle_count_from_chain_count(2));
+    assert_eq!(next.cumulative_game_score(), 3 + calculate_game_score(2));
+    //a chain offsets the pending obstacles first
+    let s = SearchState::default().with_obstacle_block_count(20);
+    let next = s.after_drop(&child(3));
+    assert_eq!(next.obstacle_block_count(), 20 - calculate_obstacle_count_from_chain_count(3));
+    assert_eq!(next.spawn_obstacle_block_count(), 0);
+}
+
 #[test]
 fn test_zobrist_hash() {
     let board = [
@@ -369,6 +449,17 @@ fn test_zobrist_hash() {
     assert_ne!(x1, x2);
 }
 
+#[test]
+fn test_search_state_size() {
+    assert!(std::mem::size_of::<SearchState>() <= SEARCH_STATE_SIZE, "{}", std::mem::size_of::<SearchState>());
+    let mut s = SearchState::default();
+    assert_eq!(s.command(), None);
+    for &command in [Command::Spell, Command::Drop((0, 0)), Command::Drop((8, 3)), Command::Drop((4, 1))].iter() {
+        s.set_command(command);
+        assert_eq!(s.command(), Some(command));
+    }
+}
+
 #[test]
 fn test_compare_search_state() {
     extern crate min_max_heap;
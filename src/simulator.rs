@@ -16,6 +16,19 @@ pub const DIRECTION_YXS: [(i8, i8); 8] = [
 ];
 
 
+//a board made by Simulator::expand
+#[derive(Debug, Copy, Clone)]
+pub struct Child {
+    pub board: board::Board,
+    pub chain_count: u8,
+    pub point: usize,
+    pub rotate_count: usize,
+    pub pack: pack::Pack,
+}
+
+//a block which explodes with a spell
+pub const SPELL_BLOCK: u8 = 5;
+
 pub struct Simulator {
     pub modified_blocks: Vec<(usize, usize)>,
     pub erase_blocks: Vec<(usize, usize)>,
@@ -53,6 +66,18 @@ impl Simulator {
         }
         chain_count
     }
+    //chains of a board which wasn't made by the simulator, every block is checked
+    pub fn resolve_chains(&mut self, board: &mut board::Board) -> u8 {
+        self.init();
+        for x in 0..FIELD_WIDTH {
+            for y in 0..board.heights[x] {
+                if board.get(y, x) != OBSTACLE_BLOCK {
+                    self.modified_blocks.push((y, x));
+                }
+            }
+        }
+        self.calculate_chain_count(board)
+    }
     pub fn simulate(&mut self, board: &mut board::Board, point: usize, pack: &pack::Pack) -> u8 {
         self.init();
         self.drop_pack(board, point, &pack);
@@ -60,6 +85,160 @@ impl Simulator {
         chain_count
     }
 
+    //every placement of every orientation in the order the solver tries them
+    //drops which can't erase anything are put on the board without resolving chains
+    //the board must be stable like every board the search makes, placements that don't fit are skipped
+    pub fn expand<'a>(
+        &'a mut self,
+        board: &'a board::Board,
+        pack_orientations: &'a [(pack::Pack, usize)],
+    ) -> impl Iterator<Item = Child> + 'a {
+        //digits which erase at the height of a column and one above it, bit d for digit d
+        let mut erasable_digits = [[0u16; 2]; FIELD_WIDTH];
+        for x in 0..FIELD_WIDTH {
+            for level in 0..2 {
+                let y = board.heights[x] + level;
+                let mut digits = 0;
+                for &(dy, dx) in DIRECTION_YXS.iter() {
+                    //the same column only has the block under the first one
+                    if dx == 0 && (level == 1 || dy != -1) {
+                        continue;
+                    }
+                    let (ny, nx) = (y as i8 + dy, x as i8 + dx);
+                    if !is_on_board(ny, nx) {
+                        continue;
+                    }
+                    let block = board.get(ny as usize, nx as usize);
+                    if block != EMPTY_BLOCK && block != OBSTACLE_BLOCK {
+                        digits |= 1 << (ERASING_SUM - block);
+                    }
+                }
+                erasable_digits[x][level] = digits;
+            }
+        }
+        let placements = pack_orientations
+            .iter()
+            .flat_map(|&(pack, rotate_count)| (0..9).map(move |point| (pack, rotate_count, point)));
+        placements.filter_map(move |(pack, rotate_count, point)| {
+            //the pack appears on the top two rows
+            if board.heights[point] + 2 > FIELD_HEIGHT || board.heights[point + 1] + 2 > FIELD_HEIGHT {
+                return None;
+            }
+            //(y, x, block) of the dropped blocks
+            let mut landed = [(0, 0, EMPTY_BLOCK); 4];
+            let mut landed_count = 0;
+            let mut can_erase = false;
+            for dx in 0..2 {
+                let x = point + dx;
+                let mut level = 0;
+                for &idx in [2 + dx, dx].iter() {
+                    let block = pack.get(idx);
+                    if block == EMPTY_BLOCK {
+                        continue;
+                    }
+                    let y = board.heights[x] + level;
+                    can_erase |= (erasable_digits[x][level] >> block) & 1 == 1;
+                    landed[landed_count] = (y, x, block);
+                    landed_count += 1;
+                    level += 1;
+                }
+            }
+            //blocks of the pack next to each other
+            for i in 0..landed_count {
+                for j in i + 1..landed_count {
+                    let (y1, x1, block1) = landed[i];
+                    let (y2, x2, block2) = landed[j];
+                    if block1 + block2 == ERASING_SUM && (y1 as i8 - y2 as i8).abs() <= 1 && (x1 as i8 - x2 as i8).abs() <= 1 {
+                        can_erase = true;
+                    }
+                }
+            }
+            let mut next_board = *board;
+            let chain_count = if can_erase {
+                self.simulate(&mut next_board, point, &pack)
+            } else {
+                for &(y, x, block) in landed[..landed_count].iter() {
+                    next_board.set(y, x, block);
+                    next_board.heights[x] = y + 1;
+                }
+                next_board.debug_check_invariants();
+                0
+            };
+            Some(Child { board: next_board, chain_count, point, rotate_count, pack })
+        })
+    }
+
+    //same as simulate but keeps the board after dropping and after every chain
+    pub fn simulate_with_stages(
+        &mut self,
+        board: &mut board::Board,
+        point: usize,
+        pack: &pack::Pack,
+    ) -> Vec<board::Board> {
+        self.init();
+        self.drop_pack(board, point, &pack);
+        let mut stages = vec![*board];
+        while !self.modified_blocks.is_empty() {
+            self.calculate_erase_blocks(&board);
+            if self.erase_blocks.is_empty() {
+                break;
+            }
+            self.apply_erase_blocks(board);
+            stages.push(*board);
+        }
+        stages
+    }
+
+    //erase every 5 and its neighbors except obstacles, then chain
+    //(erased block count, chain count)
+    pub fn spell(&mut self, board: &mut board::Board) -> (u32, u8) {
+        self.init();
+        self.calculate_spell_erase_blocks(board);
+        let erased_block_count = self.erase_blocks.len() as u32;
+        if erased_block_count == 0 {
+            return (0, 0);
+        }
+        self.apply_erase_blocks(board);
+        (erased_block_count, self.calculate_chain_count(board))
+    }
+    //same as spell but keeps the board after the explosion and after every chain
+    pub fn spell_with_stages(&mut self, board: &mut board::Board) -> (u32, Vec<board::Board>) {
+        self.init();
+        self.calculate_spell_erase_blocks(board);
+        let erased_block_count = self.erase_blocks.len() as u32;
+        let mut stages = Vec::new();
+        while !self.erase_blocks.is_empty() {
+            self.apply_erase_blocks(board);
+            stages.push(*board);
+            self.calculate_erase_blocks(board);
+        }
+        (erased_block_count, stages)
+    }
+    fn calculate_spell_erase_blocks(&mut self, board: &board::Board) {
+        self.erase_blocks.clear();
+        for x in 0..FIELD_WIDTH {
+            for y in 0..board.heights[x] {
+                if board.get(y, x) != SPELL_BLOCK {
+                    continue;
+                }
+                self.erase_blocks.push((y, x));
+                for &dyx in DIRECTION_YXS.iter() {
+                    let (ny, nx) = (y as i8 + dyx.0, x as i8 + dyx.1);
+                    if !is_on_board(ny, nx) {
+                        continue;
+                    }
+                    let neighbor_block = board.get(ny as usize, nx as usize);
+                    if neighbor_block != EMPTY_BLOCK && neighbor_block != OBSTACLE_BLOCK {
+                        self.erase_blocks.push((ny as usize, nx as usize));
+                    }
+                }
+            }
+        }
+        //unique
+        self.erase_blocks.sort();
+        self.erase_blocks.dedup();
+    }
+
     fn drop_pack(&mut self, board: &mut board::Board, point: usize, pack: &pack::Pack) {
         debug_assert!(point <= 8);
         for idx in (0..4).rev() {
@@ -76,6 +255,7 @@ impl Simulator {
             board.heights[nx] += 1;
             self.modified_blocks.push((ny, nx));
         }
+        board.debug_check_invariants();
     }
 
     fn calculate_erase_blocks(&mut self, board: &board::Board) {
@@ -139,6 +319,7 @@ impl Simulator {
         //unique
         self.modified_blocks.sort();
         self.modified_blocks.dedup();
+        board.debug_check_invariants();
     }
 }
 
@@ -164,6 +345,11 @@ pub fn calculate_game_score(chain_count: u8) -> u32 {
     CHAIN_CUMULATIVE_SCORES[chain_count as usize]
 }
 
+//25 * 2 ^ (erased block count / 12)
+pub fn calculate_skill_score(erased_block_count: u32) -> u32 {
+    (25.0 * 2f64.powf(erased_block_count as f64 / 12.0)).floor() as u32
+}
+
 pub fn calculate_obstacle_count(chain_score: u32, skill_chain_score: u32) -> u32 {
     chain_score / 2 + skill_chain_score / 2
 }
@@ -513,3 +699,138 @@ fn test_drop_pack() {
     let dropped_board = board::Board::new(dropped_board);
     debug_assert_eq!(board, dropped_board);
 }
+
+#[test]
+fn test_simulate_with_stages() {
+    let raw_board = [
+        [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 2, 0, 0, 0, 0, 0],
+        [0, 0, 0, 0, 4, 0, 0, 0, 0, 0],
+        [0, 0, 0, 7, 4, 0, 0, 0, 0, 0],
+        [0, 0, 0, 4, 4, 8, 0, 0, 0, 0],
+        [0, 0, 0, 9, 8, 4, 0, 0, 0, 0],
+        [0, 0, 0, 3, 4, 8, 9, 0, 0, 0],
+        [0, 0, 0, 5, 9, 4, 8, 0, 0, 0],
+        [0, 0, 1, 6, 3, 4, 1, 0, 0, 0],
+        [0, 0, 6, 5, 1, 2, 3, 4, 0, 0],
+        [0, 0, 1, 3, 6, 2, 2, 1, 0, 0]
+    ];
+    let pack = pack::Pack::new(&[7, 6, 6, 9]);
+    let mut board = board::Board::new(raw_board);
+    let chain_count = Simulator::new().simulate(&mut board, 6, &pack);
+
+    let mut staged_board = board::Board::new(raw_board);
+    let stages = Simulator::new().simulate_with_stages(&mut staged_board, 6, &pack);
+    debug_assert_eq!(stages.len(), chain_count as usize + 1);
+    debug_assert_eq!(staged_board, board);
+    debug_assert_eq!(*stages.last().unwrap(), board);
+    let mut dropped_board = board::Board::new(raw_board);
+    let mut simulator = Simulator::new();
+    simulator.drop_pack(&mut dropped_board, 6, &pack);
+    debug_assert_eq!(stages[0], dropped_board);
+}
+
+#[test]
+fn test_spell() {
+    let board: board::Board = "
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        .8........
+        .3........
+        21........
+        #3........
+        #4........
+        95......5.
+    "
+    .parse()
+    .unwrap();
+    let mut spelled_board = board;
+    let (erased_block_count, chain_count) = Simulator::new().spell(&mut spelled_board);
+    //5, 9 and 4 around (0, 1) and the lone 5
+    //then 2 and 8 fall next to each other
+    debug_assert_eq!((erased_block_count, chain_count), (4, 1));
+    let expected: board::Board = "
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        .3........
+        #1........
+        #3........
+    "
+    .parse()
+    .unwrap();
+    debug_assert_eq!(spelled_board, expected);
+
+    let mut staged_board = board;
+    let (erased, stages) = Simulator::new().spell_with_stages(&mut staged_board);
+    debug_assert_eq!(erased, 4);
+    debug_assert_eq!(stages.len(), chain_count as usize + 1);
+    debug_assert_eq!(staged_board, expected);
+
+    let mut no_five = expected;
+    debug_assert_eq!(Simulator::new().spell(&mut no_five), (0, 0));
+    debug_assert_eq!(calculate_skill_score(4), 31);
+    debug_assert_eq!(calculate_skill_score(24), 100);
+}
+
+
+#[test]
+fn test_expand() {
+    use crate::reference_simulator::{random_pack, random_stable_grid, to_board};
+    let mut rnd = crate::xorshift::Xorshift::with_seed(6);
+    let mut simulator = Simulator::new();
+    let mut skipped = 0;
+    for iteration in 0..150 {
+        let mut board = to_board(&random_stable_grid(&mut rnd, if iteration % 3 == 0 { 16 } else { 10 }));
+        //into the hidden rows for placements which don't fit
+        if iteration % 3 == 0 {
+            board.drop_obstacles();
+            board.drop_obstacles();
+        }
+        let pack_orientations = random_pack(&mut rnd).unique_rotations();
+        let children: Vec<Child> = simulator.expand(&board, &pack_orientations).collect();
+        let mut expected = Vec::new();
+        for &(pack, rotate_count) in pack_orientations.iter() {
+            for point in 0..9 {
+                if board.heights[point] + 2 > FIELD_HEIGHT || board.heights[point + 1] + 2 > FIELD_HEIGHT {
+                    continue;
+                }
+                let mut next_board = board;
+                let chain_count = simulator.simulate(&mut next_board, point, &pack);
+                expected.push((next_board, chain_count, point, rotate_count));
+            }
+        }
+        skipped += pack_orientations.len() * 9 - expected.len();
+        assert_eq!(children.len(), expected.len());
+        for (child, &(next_board, chain_count, point, rotate_count)) in children.iter().zip(expected.iter()) {
+            assert_eq!((child.point, child.rotate_count), (point, rotate_count));
+            assert_eq!(child.chain_count, chain_count, "{}", board);
+            assert_eq!(child.board, next_board);
+            assert_eq!(child.board.heights, next_board.heights);
+        }
+    }
+    assert!(skipped > 0);
+}
@@ -1,33 +1,40 @@
 extern crate fnv;
-extern crate min_max_heap;
 
-use self::min_max_heap::MinMaxHeap;
-use crate::board::{
-    Board, DANGER_LINE_HEIGHT, FIELD_HEIGHT, FIELD_WIDTH, INPUT_FIELD_HEIGHT, OBSTACLE_BLOCK,
-};
+use crate::attack_schedule::AttackSchedule;
+use crate::beam::Beam;
+use crate::board::{FIELD_HEIGHT, FIELD_WIDTH};
 use crate::command::Command;
+use crate::counter::{CounterAction, CounterSearch};
 use crate::evaluation::{
     evaluate_game_score_by_depth, evaluate_game_score_for_bomber, evaluate_search_result_score,
     evaluate_search_result_score_for_bomber, EvaluateCache, GAME_SCORE_DEPTH_RATES,
 };
+use crate::fire_timing::{FireAction, FireTiming};
 use crate::game_status::GameStatus;
+use crate::lethal::{Lethal, LethalCheck};
 use crate::pack::Pack;
-use crate::scanner;
 use crate::search_result::{SearchResult, FIRE_RIGHT_NOW_BOOST_SCORE};
 use crate::search_state::SearchState;
 use crate::simulator;
-use crate::simulator::Simulator;
-use crate::solver_config::{SolverConfig, DEFAULT_FATAL_FIRE_MAX_CHAIN_COUNT};
+use crate::simulator::{Child, Simulator};
+use crate::solver_config::{SearchStrategy, SolverConfig};
+use crate::survival;
+use crate::survival::SurvivalSearch;
+use crate::transposition::TranspositionSet;
 use crate::xorshift::Xorshift;
 
 
 pub struct Solver {
+    //packs as given, the referee and the fire timing search rotate them like the game
+    raw_packs: Vec<Pack>,
     packs: Vec<Vec<(Pack, usize)>>,
     player: GameStatus,
     enemy: GameStatus,
     config: SolverConfig,
     simulator: Simulator,
     evaluate_cache: EvaluateCache,
+    //made on the first use and kept, its rollout cache takes 16MiB
+    fire_timing: Option<FireTiming>,
     turn: usize,
     last_best_search_result: Option<(u8, usize)>,
     last_kill_bomber: bool,
@@ -35,17 +42,65 @@ pub struct Solver {
     debug: bool, //debug mode
 }
 
-const MAX_TURN: usize = 500;
+pub const MAX_TURN: usize = 500;
+//the lethal check gets its whole node limit with this much time left and less below, a check
+//which runs out of nodes takes about 0.3s
+const LETHAL_FULL_REST_TIME_MILLISECONDS: u32 = 45000;
+
+//a move in the beam and the index of the move before it
+#[derive(Debug, Copy, Clone)]
+pub struct Transition {
+    pub parent: u32,
+    pub point: u8,
+    pub rotate_count: u8,
+}
+
+impl Transition {
+    //the placeholder at index 0 which the root state points to
+    pub fn root() -> Transition {
+        Transition { parent: 0, point: 0, rotate_count: 0 }
+    }
+    pub fn from_child(parent: u32, child: &Child) -> Transition {
+        Transition { parent, point: child.point as u8, rotate_count: child.rotate_count as u8 }
+    }
+}
+
+//what a beam on the enemy board found
+#[derive(Debug, Clone)]
+struct EnemyForecast {
+    //the largest chain fired or left to fire on any board of the beam
+    max_chain_count: u8,
+    //the largest chain fired, at the earliest depth it was found
+    attacks: AttackSchedule,
+}
+
+pub fn principal_variation(transitions: &[Transition], history: u32) -> Vec<Command> {
+    let mut commands = Vec::new();
+    let mut idx = history as usize;
+    //0 is the root
+    while idx != 0 {
+        let transition = transitions[idx];
+        commands.push(Command::Drop((
+            transition.point as usize,
+            transition.rotate_count as usize,
+        )));
+        idx = transition.parent as usize;
+    }
+    commands.reverse();
+    commands
+}
 
 impl Solver {
     pub fn default() -> Solver {
         Solver {
+            raw_packs: Vec::new(),
             packs: Vec::new(),
             player: GameStatus::default(),
             enemy: GameStatus::default(),
             config: SolverConfig::default(),
             simulator: Simulator::default(),
             evaluate_cache: EvaluateCache::new(),
+            fire_timing: None,
             turn: 0,
             last_best_search_result: None,
             last_kill_bomber: false,
@@ -54,7 +109,7 @@ impl Solver {
         }
     }
     pub fn new(
-        packs: Vec<Vec<(Pack, usize)>>,
+        raw_packs: Vec<Pack>,
         player: GameStatus,
         enemy: GameStatus,
         config: SolverConfig,
@@ -62,12 +117,14 @@ impl Solver {
         debug: bool,
     ) -> Solver {
         Solver {
-            packs,
+            packs: raw_packs.iter().map(|pack| pack.unique_rotations()).collect(),
+            raw_packs,
             player,
             enemy,
+            evaluate_cache: EvaluateCache::with_capacity_bytes(config.evaluate_cache_bytes()),
             config,
             simulator: Simulator::new(),
-            evaluate_cache: EvaluateCache::new(),
+            fire_timing: None,
             turn: 0,
             last_best_search_result: None,
             last_kill_bomber: false,
@@ -89,31 +146,40 @@ impl Solver {
         self.seed = seed;
         self
     }
-    pub fn set_packs(&mut self, packs: Vec<Vec<(Pack, usize)>>) {
-        self.packs = packs;
+    pub fn set_packs(&mut self, raw_packs: Vec<Pack>) {
+        self.packs = raw_packs.iter().map(|pack| pack.unique_rotations()).collect();
+        self.raw_packs = raw_packs;
     }
     pub fn set_game_status(&mut self, player: GameStatus, enemy: GameStatus) {
-        //check whether solver should clear cache
-        self.clear_cache_if_needed(&player);
+        //age the cache and forget the last best result if obstacles drop
+        self.age_cache(&player);
         self.player = player;
         self.enemy = enemy;
     }
     pub fn set_config(&mut self, config: SolverConfig) {
+        if config.evaluate_cache_bytes() != self.config.evaluate_cache_bytes() {
+            self.evaluate_cache = EvaluateCache::with_capacity_bytes(config.evaluate_cache_bytes());
+        }
         self.config = config;
     }
+    pub fn evaluate_cache(&self) -> &EvaluateCache {
+        &self.evaluate_cache
+    }
+    pub fn evaluate_cache_mut(&mut self) -> &mut EvaluateCache {
+        &mut self.evaluate_cache
+    }
     pub fn set_debug(&mut self, debug: bool) {
         self.debug = debug;
     }
-    fn clear_cache_if_needed(&mut self, player: &GameStatus) {
+    fn age_cache(&mut self, player: &GameStatus) {
         if self.debug {
             eprintln!(
-                "Cache size for erasing all {}",
-                self.evaluate_cache
-                    .len_estimate_with_erasing_all_max_chain_count()
+                "Cache for erasing all: {}",
+                self.evaluate_cache.stats_estimate_with_erasing_all_max_chain_count()
             );
             eprintln!(
-                "Cache size for max chain count {}",
-                self.evaluate_cache.len_estimate_max_chain_count()
+                "Cache for max chain count: {}",
+                self.evaluate_cache.stats_estimate_max_chain_count()
             );
         }
 
@@ -126,178 +192,21 @@ impl Solver {
             //enemy spawn new obstacle block
             if line_block > 0 {
                 if self.debug {
-                    eprintln!("Cache Clear because board get dirty!!");
+                    eprintln!("Forget the last best result because board get dirty!!");
                 }
-                self.evaluate_cache.clear();
                 self.last_best_search_result = None;
-                //debug_assert!(self.evaluate_cache.empty());
             }
         }
-        //512MB
-        if self.evaluate_cache.len_estimate_max_chain_count() > 512 * 1024 * 1024 {
-            eprintln!(
-                "Cache Clear because cache is too big: {}",
-                self.evaluate_cache.len_estimate_max_chain_count()
-            );
-            self.evaluate_cache.clear();
-        }
-        if self
-            .evaluate_cache
-            .len_estimate_with_erasing_all_max_chain_count()
-            > 512 * 1024 * 1024
-        {
-            eprintln!(
-                "Cache Clear because cache is too big: {}",
-                self.evaluate_cache.len_estimate_max_chain_count()
-            );
-            self.evaluate_cache.clear();
-        }
+        self.evaluate_cache.new_generation();
     }
 
-    pub fn read_packs<R: std::io::Read>(sc: &mut scanner::Scanner<R>) -> Vec<Vec<(Pack, usize)>> {
-        (0..MAX_TURN)
-            .map(|_| {
-                let mut blocks = [0; 4];
-                for i in 0..4 {
-                    blocks[i] = sc.read::<u8>();
-                }
-                let end: String = sc.read();
-                debug_assert_eq!(end, "END");
-
-                let mut pack_set = fnv::FnvHashSet::default();
-                let mut res = Vec::new();
-                for i in 0..4 {
-                    let mut pack = Pack::new(&blocks);
-                    pack.rotates(i);
-                    //To make pack unique
-                    //5 8  0 8
-                    //0 5  5 5
-                    pack.drop();
-                    if pack_set.contains(&pack) {
-                        continue;
-                    }
-                    res.push((pack.clone(), i));
-                    pack_set.insert(pack);
-                }
-                res
-            })
-            .collect()
-    }
-
-    pub fn read_game_status<R: std::io::Read>(sc: &mut scanner::Scanner<R>) -> GameStatus {
-        //read player data
-        let rest_time_milliseconds: u32 = sc.read();
-        let obstacle_block_count: u32 = sc.read();
-        let skill_point: u32 = sc.read();
-        let cumulative_game_score: u32 = sc.read();
-
-        let mut input_board: [[u8; FIELD_WIDTH]; INPUT_FIELD_HEIGHT] =
-            [[0; FIELD_WIDTH]; INPUT_FIELD_HEIGHT];
-        for y in 0..INPUT_FIELD_HEIGHT {
-            for x in 0..FIELD_WIDTH {
-                input_board[y][x] = sc.read::<u8>();
-            }
-        }
-        let end: String = sc.read();
-        debug_assert_eq!(end, "END");
-        let board = Board::new(input_board);
-        GameStatus::default()
-            .with_rest_time_milliseconds(rest_time_milliseconds)
-            .with_obstacle_block_count(obstacle_block_count)
-            .with_skill_point(skill_point)
-            .with_cumulative_game_score(cumulative_game_score)
-            .with_board(board)
-    }
     pub fn output_command(command: Command) {
-        match command {
-            Command::Drop(v) => {
-                println!("{} {}", v.0, v.1);
-            }
-            Command::Spell => {
-                println!("S");
-            }
-        }
+        println!("{}", command);
     }
 
-    fn should_fire_right_now(
-        &self,
-        chain_count: u8,
-        max_enemy_chain_count: u8,
-        need_kill_chain_count: u8,
-    ) -> bool {
-        if chain_count > max_enemy_chain_count {
-            return false;
-        }
-        //chain count is fatal max chain count
-        if chain_count >= need_kill_chain_count {
-            if self.debug {
-                eprintln!(
-                    "Fire!!: A chain count is over to kill enemy!!: {} {}",
-                    chain_count, need_kill_chain_count
-                );
-            }
-            return true;
-        }
-
-
-        if self.enemy.obstacle_block_count() < 10 && chain_count >= max_enemy_chain_count {
-            let enemy_obstacle_count =
-                simulator::calculate_obstacle_count_from_chain_count(max_enemy_chain_count);
-            let total_enemy_obstacle_count =
-                enemy_obstacle_count + self.enemy.obstacle_block_count();
-            let player_obstacle_count =
-                simulator::calculate_obstacle_count_from_chain_count(chain_count);
-            if player_obstacle_count > self.player.obstacle_block_count() {
-                let player_spawned_obstacle_count =
-                    player_obstacle_count - self.player.obstacle_block_count();
-                if player_spawned_obstacle_count <= total_enemy_obstacle_count {
-                    return false;
-                }
-                let spawned_obstacle_count =
-                    player_spawned_obstacle_count - total_enemy_obstacle_count;
-                let line_block = simulator::calculate_spawn_obstacle_line_from_obstacle_count(
-                    spawned_obstacle_count,
-                );
-                if line_block >= 3 {
-                    if self.debug {
-                        eprintln!(
-                            "Fire!!: Must spawn three line!! {}",
-                            player_spawned_obstacle_count
-                        );
-                    }
-                    return true;
-                }
-            }
-        }
-
-        false
-    }
-    fn gaze_enemy_need_kill_chain_count(&self) -> u8 {
-        let enemy = &self.enemy;
-        let board = enemy.board();
-        let mut spawned_obstacle_count = 0;
-        for x in 0..FIELD_WIDTH {
-            for y in (0..board.heights[x]).rev() {
-                if board.get(y, x) == OBSTACLE_BLOCK {
-                    spawned_obstacle_count = std::cmp::max(spawned_obstacle_count, y + 1);
-                    break;
-                }
-            }
-        }
-        let need_kill_line = (DANGER_LINE_HEIGHT - spawned_obstacle_count) as u8;
-        for chain_count in 0..DEFAULT_FATAL_FIRE_MAX_CHAIN_COUNT {
-            let spawned_obstacle_count =
-                simulator::calculate_obstacle_count_from_chain_count(chain_count);
-            let spawned_line = (spawned_obstacle_count / 10) as u8;
-            if spawned_line >= need_kill_line {
-                return chain_count;
-            }
-        }
-        DEFAULT_FATAL_FIRE_MAX_CHAIN_COUNT
-    }
-    #[allow(dead_code)]
-    fn gaze_enemy_max_chain_count_by_beam_search(&mut self, spawn_obstacle: u32, beam_depth: usize, beam_width: usize) -> u8 {
-        let mut search_state_heap: Vec<MinMaxHeap<SearchState>> = (0..beam_depth + 1).map(|_| MinMaxHeap::new()).collect();
+    //a beam on the enemy board, spawn_obstacle lands on it before its second drop
+    fn gaze_enemy_by_beam_search(&mut self, spawn_obstacle: u32, beam_depth: usize, beam_width: usize) -> EnemyForecast {
+        let mut search_state_heap: Vec<Beam> = (0..beam_depth + 1).map(|_| Beam::with_capacity(beam_width + 1)).collect();
         let root_search_state = SearchState::default()
             .with_board(self.enemy.board())
             .with_obstacle_block_count(self.enemy.obstacle_block_count())
@@ -306,84 +215,70 @@ impl Solver {
         //push an initial search state
         search_state_heap[0].push(root_search_state);
         let mut rnd = Xorshift::with_seed(self.turn() as u64 + self.seed);
-        let mut searched_state = fnv::FnvHashSet::default();
+        let mut searched_state = TranspositionSet::new();
         let mut max_chain_count = 0;
+        //chain count, depth and obstacles sent after offsetting the enemy's own
+        let mut best_fire: Option<(u8, usize, u32)> = None;
+        let mut children = Vec::with_capacity(4 * 9);
         for depth in 0..beam_depth {
             let search_turn = self.turn() + depth;
-            while let Some(search_state) = &mut search_state_heap[depth].pop_max() {
+            if search_turn >= self.packs.len() {
+                break;
+            }
+            while let Some(mut search_state) = search_state_heap[depth].pop_max() {
                 if depth == 1 {
                     let count = search_state.obstacle_block_count();
                     search_state.set_obstacle_block_count(count + spawn_obstacle);
                 }
                 search_state.update_obstacle_block_and_drop();
+                search_state.board().debug_check_invariants();
+                children.clear();
+                children.extend(self.simulator.expand(&search_state.board(), &self.packs[search_turn]));
+                for child in children.iter() {
+                    let chain_count = child.chain_count;
+                    max_chain_count = std::cmp::max(max_chain_count, chain_count);
+                    let (estimated_chain_count, _) = self.evaluate_cache.estimate_with_erasing_all_max_chain_count(&mut self.simulator, &child.board);
+                    max_chain_count = std::cmp::max(max_chain_count, estimated_chain_count);
+                    //Next board is dead and not to put it in state heap
+                    if child.board.is_game_over() {
+                        continue;
+                    }
+                    let mut next_search_state = search_state.after_drop(child);
+                    if chain_count > 0 && best_fire.is_none_or(|(best_chain_count, _, _)| chain_count > best_chain_count) {
+                        let sent = next_search_state
+                            .spawn_obstacle_block_count()
+                            .saturating_sub(search_state.spawn_obstacle_block_count());
+                        best_fire = Some((chain_count, depth, sent));
+                    }
 
-                for (pack, rotate_count) in self.packs[search_turn].iter() {
-                    for point in 0..9 {
-                        let mut board = search_state.board();
-                        let chain_count = self.simulator.simulate(&mut board, point, &pack);
-                        max_chain_count = std::cmp::max(max_chain_count, chain_count);
-                        let (estimated_chain_count, _) = self.evaluate_cache.estimate_with_erasing_all_max_chain_count(&mut self.simulator, &board);
-                        max_chain_count = std::cmp::max(max_chain_count, estimated_chain_count);
-                        //Next board is dead and not to put it in state heap
-                        if board.is_game_over() {
-                            continue;
-                        }
-
-                        //update these values
-                        let gain_chain_game_score = simulator::calculate_game_score(chain_count);
-                        let next_board = board;
-                        let next_cumulative_game_score =
-                            gain_chain_game_score + search_state.cumulative_game_score();
-                        let next_spawn_obstacle_block_count =
-                            simulator::calculate_obstacle_count_from_chain_count(chain_count)
-                                + search_state.spawn_obstacle_block_count();
-                        //create next search state from a previous state
-                        let mut next_search_state = search_state
-                            .clone()
-                            .with_board(next_board)
-                            .with_cumulative_game_score(next_cumulative_game_score)
-                            .with_spawn_obstacle_block_count(next_spawn_obstacle_block_count);
-
-                        next_search_state.update_obstacle_block();
-                        if !next_search_state.is_command() {
-                            debug_assert_eq!(depth, 0);
-                            next_search_state.set_command(Command::Drop((point, *rotate_count)));
-                        }
-
-                        //remove duplication
-                        if searched_state.contains(&next_search_state.zobrist_hash()) {
-                            continue;
-                        }
-                        //push it to hash set
-                        searched_state.insert(next_search_state.zobrist_hash());
-                        debug_assert_eq!(
-                            search_state.cumulative_game_score() + gain_chain_game_score,
-                            next_search_state.cumulative_game_score()
-                        );
-
-                        // Add a tiny value(0.0 ~ 1.0) to search score
-                        // To randomize search score for the diversity of search
-                        let next_search_score = self
-                            .evaluate_cache
-                            .evaluate_search_score(&mut self.simulator, &next_search_state)
-                            + rnd.randf();
-                        next_search_state.set_search_score(next_search_score);
+                    //remove duplication
+                    if !searched_state.insert(&next_search_state) {
+                        continue;
+                    }
 
-                        //push it to next beam
-                        //prune fire state
-                        if chain_count <= 10 {
-                            search_state_heap[depth + 1].push(next_search_state);
-                            //The number of next beam is over beam_width; pop minimum state
-                            while search_state_heap[depth + 1].len() > beam_width {
-                                search_state_heap[depth + 1].pop_min();
-                            }
-                            debug_assert!(search_state_heap[depth + 1].len() <= beam_width);
-                        }
+                    // Add a tiny value(0.0 ~ 1.0) to search score
+                    // To randomize search score for the diversity of search
+                    let next_search_score = self
+                        .evaluate_cache
+                        .evaluate_search_score(&mut self.simulator, &next_search_state)
+                        + rnd.randf();
+                    next_search_state.set_search_score(next_search_score);
+
+                    //push it to next beam
+                    //prune fire state, a fired board is spent
+                    if chain_count <= 10 {
+                        //The number of next beam is over beam_width; pop minimum state
+                        search_state_heap[depth + 1].push_bounded(next_search_state, beam_width);
+                        debug_assert!(search_state_heap[depth + 1].len() <= beam_width);
                     }
                 }
             }
         }
-        max_chain_count
+        let mut attacks = AttackSchedule::new();
+        if let Some((_, depth, sent)) = best_fire {
+            attacks.add_fire(depth, sent);
+        }
+        EnemyForecast { max_chain_count, attacks }
     }
     //gaze enemy
     #[allow(dead_code)]
@@ -399,6 +294,43 @@ impl Solver {
         }
         max_chain_count
     }
+    //the enemy fires the largest chain its beam finds, at the earliest depth it finds it
+    pub fn predict_enemy_attacks(&mut self, beam_depth: usize, beam_width: usize) -> AttackSchedule {
+        self.gaze_enemy_by_beam_search(0, beam_depth, beam_width).attacks
+    }
+    //whether firing the chain now wins more rollouts than building on
+    fn fire_timing_prefers_fire(&mut self, chain_count: u8) -> bool {
+        let mut fire_timing = self
+            .fire_timing
+            .take()
+            .unwrap_or_else(FireTiming::default)
+            .with_iterations(self.config.fire_timing_iterations())
+            .with_seed(self.turn as u64 + self.seed);
+        let result = fire_timing.search(&self.raw_packs, self.turn, &self.player, &self.enemy);
+        self.fire_timing = Some(fire_timing);
+        if self.debug {
+            for stats in result.player.iter() {
+                eprintln!("{}: {:.3} in {} rollouts", stats.action, stats.win_probability(), stats.visits);
+            }
+        }
+        let win_probability = |fire: bool| {
+            result
+                .player
+                .iter()
+                .filter(|stats| match stats.action {
+                    FireAction::Fire(_, fire_chain_count) => fire && fire_chain_count == chain_count,
+                    FireAction::Build(_) => !fire,
+                    FireAction::Spell => false,
+                })
+                .map(|stats| stats.win_probability())
+                .next()
+        };
+        match (win_probability(true), win_probability(false)) {
+            (Some(fire), Some(build)) => fire > build,
+            (Some(_), None) => true,
+            _ => false,
+        }
+    }
     pub fn beam_search_config(&self) -> (usize, usize) {
         let player = &self.player;
 
@@ -486,6 +418,86 @@ impl Solver {
         }
         false
     }
+    fn lethal_node_limit(&self) -> usize {
+        let rest_time_milliseconds = std::cmp::min(self.player.rest_time_milliseconds(), LETHAL_FULL_REST_TIME_MILLISECONDS);
+        self.config.lethal_node_limit() * rest_time_milliseconds as usize / LETHAL_FULL_REST_TIME_MILLISECONDS as usize
+    }
+    //whether firing the chain this turn kills the enemy whatever it does, unknown without the time
+    pub fn lethal(&mut self, chain_count: u8) -> Lethal {
+        LethalCheck::default().with_node_limit(self.lethal_node_limit()).check_chain(
+            &mut self.simulator,
+            &self.packs,
+            self.turn,
+            &self.player,
+            &self.enemy,
+            chain_count,
+        )
+    }
+    //offset or counter kill the pending obstacles, None to absorb them and build on
+    fn counter(&mut self) -> Option<SearchResult> {
+        let plans = CounterSearch::default().with_seed(self.seed).search(
+            &mut self.simulator,
+            &mut self.evaluate_cache,
+            &self.packs,
+            self.turn,
+            &self.player,
+            &self.enemy,
+        );
+        let plan = CounterSearch::choose(&plans)?;
+        if self.debug {
+            for plan in plans.iter() {
+                eprintln!(
+                    "Counter {}: chain {} at depth {}, sends {}, keeps {}",
+                    plan.action,
+                    plan.chain_count,
+                    plan.depth(),
+                    plan.sent_obstacle_block_count,
+                    plan.counter_obstacle_block_count
+                );
+            }
+            eprintln!("Counter: {}", plan.action);
+        }
+        if plan.action == CounterAction::Absorb {
+            return None;
+        }
+        let mut result = SearchResult::default();
+        result.command = plan.command();
+        result.principal_variation = plan.principal_variation.clone();
+        result.last_chain_count = plan.chain_count;
+        result.gain_game_score = simulator::calculate_game_score(plan.chain_count);
+        result.cumulative_game_score = self.player.cumulative_game_score() + result.gain_game_score;
+        result.search_depth = plan.depth();
+        result.board = plan.board;
+        self.last_best_search_result = None;
+        Some(result)
+    }
+    //the plan which lasts the most turns under the pending and the predicted obstacles
+    fn survive(&mut self, enemy_attacks: &AttackSchedule) -> Option<SearchResult> {
+        let plan = SurvivalSearch::default().search(
+            &mut self.simulator,
+            &self.packs,
+            self.turn,
+            &self.player,
+            &self.enemy,
+            enemy_attacks,
+        )?;
+        if self.debug {
+            eprintln!(
+                "Survival: survives {} turns in {}, clears {}, room {}",
+                plan.survived_turns,
+                plan.turns(),
+                plan.cleared_obstacle_block_count,
+                plan.room
+            );
+        }
+        let mut result = SearchResult::default();
+        result.command = plan.command();
+        result.principal_variation = plan.principal_variation.clone();
+        result.search_depth = plan.turns() - 1;
+        result.board = plan.board;
+        self.last_best_search_result = None;
+        Some(result)
+    }
     pub fn think(&mut self) -> SearchResult {
         self.last_kill_bomber = false;
         let current_turn = self.turn();
@@ -501,6 +513,7 @@ impl Solver {
                 eprintln!("Sepll Magic!!");
             }
             best_search_result.command = Command::Spell;
+            best_search_result.principal_variation = vec![Command::Spell];
             self.last_best_search_result = None;
             return best_search_result;
         }
@@ -509,6 +522,14 @@ impl Solver {
             eprintln!("Turn: {}", current_turn);
             eprintln!("Rest Time(msec): {}", self.player.rest_time_milliseconds());
         }
+        if self.config.counter_search() {
+            if let Some(result) = self.counter() {
+                return result;
+            }
+        }
+        //the survival search only steps in after the beam search, see below
+        let in_danger = self.config.survival_search()
+            && survival::in_danger(&self.player.board(), self.player.obstacle_block_count());
 
         // beam search for a command
         let (beam_depth, beam_width): (usize, usize) = self.beam_search_config();
@@ -554,9 +575,11 @@ impl Solver {
         if self.debug {
             eprintln!("Beam depth: {}, Beam width: {}", beam_depth, beam_width);
         }
-        let mut search_state_heap: Vec<MinMaxHeap<SearchState>> =
-            (0..beam_depth + 1).map(|_| MinMaxHeap::new()).collect();
-        let mut searched_state = fnv::FnvHashSet::default();
+        let mut search_state_heap: Vec<Beam> =
+            (0..beam_depth + 1).map(|_| Beam::with_capacity(beam_width + 1)).collect();
+        //collisions are only counted in debug mode, it keeps every state
+        let mut searched_state =
+            if self.debug { TranspositionSet::with_collision_count() } else { TranspositionSet::new() };
 
         //Create an initial state
         let root_search_state = SearchState::default()
@@ -569,7 +592,15 @@ impl Solver {
         let mut rnd = Xorshift::with_seed(current_turn as u64 + self.seed);
 
         //gaze enemy...
-
+        let enemy_attacks = if self.config.enemy_attack_prediction() {
+            let (gaze_beam_depth, gaze_beam_width) = self.config.gaze_beam();
+            self.predict_enemy_attacks(std::cmp::min(gaze_beam_depth, beam_depth), gaze_beam_width)
+        } else {
+            AttackSchedule::new()
+        };
+        if self.debug {
+            eprintln!("Predicted enemy attacks: {}", enemy_attacks);
+        }
 
         if self.debug {
             eprintln!(
@@ -579,54 +610,69 @@ impl Solver {
         }
 
         let mut best_immediate_fire: Option<SearchResult> = None;
-        for depth in 0..beam_depth {
-            //next state
-            let search_turn = current_turn + depth;
-            if best_search_result.fire_right_now {
-                eprintln!("Fire right now!!");
-                break;
-            }
-            while let Some(search_state) = &mut search_state_heap[depth].pop_max() {
-                //Update obstacle block
-                search_state.update_obstacle_block_and_drop();
-                //skip duplicate
-
-                for (pack, rotate_count) in self.packs[search_turn].iter() {
-                    for point in 0..9 {
-                        let mut board = search_state.board();
-                        let chain_count = self.simulator.simulate(&mut board, point, &pack);
+        let mut command_scores = SearchResult::default();
+        let mut transitions = vec![Transition::root()];
+        let mut best_history = 0;
+        let mut children = Vec::with_capacity(4 * 9);
+        //a beam search empties every depth in one pass, Chokudai search takes a few states of every
+        //depth and repeats passes until its budget runs out
+        let strategy = self.config.search_strategy();
+        let pop_count = match strategy {
+            SearchStrategy::Beam => usize::MAX,
+            SearchStrategy::Chokudai { width, .. } => width,
+        };
+        let search_start = std::time::Instant::now();
+        let mut expanded_count = 0;
+        let mut pass_count = 0;
+        'search: loop {
+            pass_count += 1;
+            let pass_start_expanded_count = expanded_count;
+            for depth in 0..beam_depth {
+                //next state
+                let search_turn = current_turn + depth;
+                if best_search_result.fire_right_now {
+                    eprintln!("Fire right now!!");
+                    break 'search;
+                }
+                for _ in 0..pop_count {
+                    let mut search_state = match search_state_heap[depth].pop_max() {
+                        Some(search_state) => search_state,
+                        None => break,
+                    };
+                    expanded_count += 1;
+                    //the predicted enemy attack arrives before the drop
+                    let attack = enemy_attacks.obstacle_block_count(depth);
+                    if attack > 0 {
+                        search_state.set_obstacle_block_count(search_state.obstacle_block_count() + attack);
+                    }
+                    //Update obstacle block
+                    search_state.update_obstacle_block_and_drop();
+                    search_state.board().debug_check_invariants();
+                    //skip duplicate
+
+                    //placements are expanded first, evaluation below needs the simulator too
+                    let board = search_state.board();
+                    children.clear();
+                    children.extend(self.simulator.expand(&board, &self.packs[search_turn]));
+                    for child in children.iter() {
+                        let (board, chain_count) = (child.board, child.chain_count);
+                        let (point, rotate_count) = (child.point, child.rotate_count);
                         //Next board is dead and not to put it in state heap
                         if board.is_game_over() {
                             continue;
                         }
 
-                        //update these values
                         let gain_chain_game_score = simulator::calculate_game_score(chain_count);
-                        let next_board = board;
-                        let next_cumulative_game_score =
-                            gain_chain_game_score + search_state.cumulative_game_score();
-                        let next_spawn_obstacle_block_count =
-                            simulator::calculate_obstacle_count_from_chain_count(chain_count)
-                                + search_state.spawn_obstacle_block_count();
-                        //create next search state from a previous state
-                        let mut next_search_state = search_state
-                            .clone()
-                            .with_board(next_board)
-                            .with_cumulative_game_score(next_cumulative_game_score)
-                            .with_spawn_obstacle_block_count(next_spawn_obstacle_block_count);
-
-                        next_search_state.update_obstacle_block();
+                        let mut next_search_state = search_state.after_drop(child);
                         if !next_search_state.is_command() {
                             debug_assert_eq!(depth, 0);
-                            next_search_state.set_command(Command::Drop((point, *rotate_count)));
+                            next_search_state.set_command(Command::Drop((point, rotate_count)));
                         }
 
                         //remove duplication
-                        if searched_state.contains(&next_search_state.zobrist_hash()) {
+                        if !searched_state.insert(&next_search_state) {
                             continue;
                         }
-                        //push it to hash set
-                        searched_state.insert(next_search_state.zobrist_hash());
                         debug_assert_eq!(
                             search_state.cumulative_game_score() + gain_chain_game_score,
                             next_search_state.cumulative_game_score()
@@ -639,15 +685,14 @@ impl Solver {
                             .evaluate_search_score(&mut self.simulator, &next_search_state)
                             + rnd.randf();
                         next_search_state.set_search_score(next_search_score);
+                        transitions.push(Transition::from_child(search_state.history(), child));
+                        next_search_state.set_history((transitions.len() - 1) as u32);
 
                         //push it to next beam
                         //prune fire state
                         if chain_count <= 10 {
-                            search_state_heap[depth + 1].push(next_search_state);
                             //The number of next beam is over beam_width; pop minimum state
-                            while search_state_heap[depth + 1].len() > beam_width {
-                                search_state_heap[depth + 1].pop_min();
-                            }
+                            search_state_heap[depth + 1].push_bounded(next_search_state, beam_width);
                             debug_assert!(search_state_heap[depth + 1].len() <= beam_width);
                         }
 
@@ -665,6 +710,10 @@ impl Solver {
                                 std::cmp::min(20, target_enemy_chain_count),
                             )
                         };
+                        command_scores.update_command_score(
+                            next_search_state.command().unwrap(),
+                            target_search_result_score,
+                        );
                         if depth == 0 && self.player.obstacle_block_count() == 0 && chain_count >= 11 {
                             let mut tmp = SearchResult::default();
                             tmp.search_result_score = target_search_result_score;
@@ -674,7 +723,10 @@ impl Solver {
                             tmp.last_chain_count = chain_count;
                             tmp.search_depth = depth;
                             tmp.board = next_search_state.board();
+                            tmp.pending_obstacle_block_count =
+                                survival::pending_obstacle_block_count(&next_search_state, depth, &enemy_attacks);
                             tmp.command = next_search_state.command().unwrap();
+                            tmp.principal_variation = vec![tmp.command];
                             if let Some(result) = best_immediate_fire.clone() {
                                 if tmp.last_chain_count > result.last_chain_count {
                                     best_immediate_fire = Some(tmp);
@@ -696,16 +748,46 @@ impl Solver {
                             best_search_result.last_chain_count = chain_count;
                             best_search_result.search_depth = depth;
                             best_search_result.board = next_search_state.board();
+                            best_search_result.pending_obstacle_block_count =
+                                survival::pending_obstacle_block_count(&next_search_state, depth, &enemy_attacks);
                             best_search_result.command = next_search_state.command().unwrap();
+                            best_history = next_search_state.history();
                         }
                     }
                 }
             }
+            match strategy {
+                SearchStrategy::Beam => break,
+                SearchStrategy::Chokudai { time_limit, node_limit, .. } => {
+                    //a pass which expands nothing, as with width 0, never expands anything again
+                    let exhausted = expanded_count == pass_start_expanded_count
+                        || search_state_heap[..beam_depth].iter().all(|beam| beam.is_empty());
+                    if exhausted || expanded_count >= node_limit || search_start.elapsed() >= time_limit {
+                        break;
+                    }
+                }
+            }
+        }
+        if self.debug {
+            eprintln!("Passes: {}, expanded states: {}", pass_count, expanded_count);
         }
+        best_search_result.principal_variation = principal_variation(&transitions, best_history);
+        let mut lethal_fire = false;
         if let Some(result) = best_immediate_fire {
-            if self.player.rest_time_milliseconds() >= 45000 && target_enemy_chain_count < 19 {
+            let lethal = if self.config.lethal_check() { self.lethal(result.last_chain_count) } else { Lethal::Unknown };
+            if self.debug && self.config.lethal_check() {
+                eprintln!("Lethal check of {} chains: {}", result.last_chain_count, lethal);
+            }
+            if lethal.is_lethal() {
+                best_search_result = result;
+                lethal_fire = true;
+            } else if self.config.fire_timing_iterations() > 0 {
+                if self.fire_timing_prefers_fire(result.last_chain_count) {
+                    best_search_result = result;
+                }
+            } else if self.player.rest_time_milliseconds() >= 45000 && target_enemy_chain_count < 19 {
                 let spawn_obstacle = simulator::calculate_obstacle_count_from_chain_count(result.last_chain_count);
-                let max_chain_count = self.gaze_enemy_max_chain_count_by_beam_search(spawn_obstacle, 5, 500);
+                let max_chain_count = self.gaze_enemy_by_beam_search(spawn_obstacle, 5, 500).max_chain_count;
                 if self.debug {
                     eprintln!("player_chain_count: {}, max_chain_count: {}", result.last_chain_count, max_chain_count);
                 }
@@ -714,7 +796,28 @@ impl Solver {
                 }
             }
         }
+        if in_danger && !lethal_fire {
+            //the board of the plan has to outlast the lines still to fall after it, which a fire offsets
+            let pending = best_search_result.pending_obstacle_block_count;
+            let (fallen_lines, _) = survival::fall_pending_lines(&best_search_result.board, pending);
+            let survives = !best_search_result.principal_variation.is_empty()
+                && fallen_lines == (pending / FIELD_WIDTH as u32) as usize;
+            if self.debug {
+                eprintln!("In danger, the search result survives: {}", survives);
+            }
+            if !survives {
+                if let Some(result) = self.survive(&enemy_attacks) {
+                    return result;
+                }
+            }
+        }
+        best_search_result.command_scores = command_scores.command_scores;
         if self.debug {
+            eprintln!(
+                "States: {}, key collisions: {}",
+                searched_state.len(),
+                searched_state.collision_count()
+            );
             eprintln!("== Search Result ==");
             best_search_result.log();
         }
@@ -727,3 +830,176 @@ impl Solver {
     }
 }
 
+#[test]
+fn test_principal_variation() {
+    let packs = crate::test_fixtures::random_packs(7);
+    let mut solver = Solver::default();
+    solver.set_packs(packs);
+    solver.set_game_status(
+        GameStatus::default().with_rest_time_milliseconds(180000),
+        GameStatus::default().with_rest_time_milliseconds(180000),
+    );
+    solver.set_config(SolverConfig::default().with_beam(4, 30));
+    let result = solver.think();
+    assert_eq!(result.principal_variation.len(), result.search_depth + 1);
+    assert_eq!(result.principal_variation[0], result.command);
+    let best_score = result.command_score(result.command).unwrap();
+    assert!(result.command_scores.iter().all(|&(_, score)| score <= best_score));
+}
+
+#[test]
+fn test_chokudai_search() {
+    let packs = crate::test_fixtures::random_packs(7);
+    //only the node budget stops it, so it is deterministic
+    let strategy = SearchStrategy::Chokudai {
+        width: 1,
+        time_limit: std::time::Duration::from_secs(3600),
+        node_limit: 30,
+    };
+    let think = || {
+        let mut solver = Solver::default();
+        solver.set_packs(packs.clone());
+        solver.set_game_status(
+            GameStatus::default().with_rest_time_milliseconds(180000),
+            GameStatus::default().with_rest_time_milliseconds(180000),
+        );
+        let config = SolverConfig::default().with_beam(6, 30).with_evaluate_cache_bytes(1 << 20);
+        solver.set_config(config.with_search_strategy(strategy));
+        solver.think()
+    };
+    let result = think();
+    assert_eq!(result.principal_variation.len(), result.search_depth + 1);
+    assert_eq!(result.principal_variation[0], result.command);
+    assert_eq!(think().principal_variation, result.principal_variation);
+
+    //width 0 expands nothing and stops instead of waiting for the time limit
+    let strategy = SearchStrategy::Chokudai {
+        width: 0,
+        time_limit: std::time::Duration::from_secs(3600),
+        node_limit: usize::MAX,
+    };
+    let mut solver = Solver::default();
+    solver.set_packs(packs.clone());
+    solver.set_config(SolverConfig::default().with_beam(6, 30).with_evaluate_cache_bytes(1 << 20).with_search_strategy(strategy));
+    let start = std::time::Instant::now();
+    solver.think();
+    assert!(start.elapsed() < std::time::Duration::from_secs(60));
+}
+
+#[test]
+fn test_predict_enemy_attacks() {
+    let board = crate::test_fixtures::seven_next_to_three();
+    let packs = vec![Pack::new(&[7, 0, 0, 0]); MAX_TURN];
+    let mut solver = Solver::default();
+    solver.set_packs(packs.clone());
+    solver.set_game_status(GameStatus::default(), GameStatus::default().with_board(board));
+    //the largest chain right now is the predicted attack
+    let mut simulator = Simulator::new();
+    let max_chain_count = simulator.expand(&board, &packs[0].unique_rotations()).map(|child| child.chain_count).max().unwrap();
+    assert!(max_chain_count >= 2);
+    let attacks = solver.predict_enemy_attacks(1, 50);
+    let obstacle_block_count = simulator::calculate_obstacle_count_from_chain_count(max_chain_count);
+    assert_eq!(attacks.iter().collect::<Vec<_>>(), vec![(1, obstacle_block_count)]);
+    //the enemy's own obstacles are offset first
+    solver.set_game_status(GameStatus::default(), GameStatus::default().with_board(board).with_obstacle_block_count(1));
+    assert_eq!(solver.predict_enemy_attacks(1, 50).total_obstacle_block_count(), obstacle_block_count - 1);
+    assert!(solver.predict_enemy_attacks(3, 50).total_obstacle_block_count() >= obstacle_block_count - 1);
+
+    //an enemy 9 chains away changes the plan once its attack is injected
+    let enemy_board = crate::test_fixtures::nine_chains();
+    let mut packs = crate::test_fixtures::random_packs(7);
+    packs[0] = Pack::new(&[0, 7, 5, 6]);
+    let think = |enemy_attack_prediction: bool| {
+        let mut solver = Solver::default();
+        solver.set_packs(packs.clone());
+        solver.set_game_status(
+            GameStatus::default().with_rest_time_milliseconds(180000),
+            GameStatus::default().with_board(enemy_board),
+        );
+        let config = SolverConfig::default().with_beam(4, 30).with_evaluate_cache_bytes(1 << 20);
+        solver.set_config(config.with_enemy_attack_prediction(enemy_attack_prediction));
+        solver.think()
+    };
+    let (ignored, injected) = (think(false), think(true));
+    assert_eq!(injected.principal_variation.len(), injected.search_depth + 1);
+    assert_eq!(injected.principal_variation[0], injected.command);
+    assert_ne!(injected.command, ignored.command);
+    assert!(injected.search_result_score < ignored.search_result_score);
+}
+
+#[test]
+fn test_raw_packs() {
+    //a diagonal pack falls into a shape its dropped rotation 0 doesn't rotate into
+    let packs = vec![Pack::new(&[5, 0, 0, 8]); MAX_TURN];
+    let mut solver = Solver::default();
+    solver.set_packs(packs.clone());
+    assert_ne!(solver.packs[0][0].0.rotated(1), packs[0].rotated(1));
+    //the referee on the raw packs plays every orientation the search expands
+    let mut simulator = Simulator::new();
+    let board = crate::board::Board::default();
+    let children: Vec<_> = simulator.expand(&board, &solver.packs[0]).collect();
+    for child in children {
+        let command = Command::Drop((child.point, child.rotate_count));
+        let mut referee = crate::referee::Referee::new(solver.raw_packs.clone());
+        referee.step(&mut simulator, [command, command]);
+        assert_eq!(referee.player(0).board(), child.board, "{}", command);
+    }
+}
+
+
+#[test]
+fn test_lethal() {
+    let wall = crate::test_fixtures::wall();
+    let mut solver = Solver::default();
+    solver.set_packs(vec![Pack::new(&[0, 1, 0, 0]); MAX_TURN]);
+    solver.set_game_status(GameStatus::default().with_rest_time_milliseconds(180000), GameStatus::default().with_board(wall));
+    assert!(solver.lethal(8).is_lethal());
+    //no time left for the check
+    solver.set_game_status(GameStatus::default(), GameStatus::default().with_board(wall));
+    assert_eq!(solver.lethal(8), Lethal::Unknown);
+}
+
+#[test]
+fn test_survival_after_beam_search() {
+    let board = crate::test_fixtures::two_lines_from_top();
+    //2s erase nothing on the board
+    let packs = vec![Pack::new(&[2, 2, 0, 0]); MAX_TURN];
+    let think = |player: GameStatus, packs: &[Pack], survival_search: bool| {
+        let mut solver = Solver::default();
+        solver.set_packs(packs.to_vec());
+        solver.set_game_status(player, GameStatus::default().with_rest_time_milliseconds(180000));
+        let config = SolverConfig::default().with_beam(4, 30).with_evaluate_cache_bytes(1 << 20);
+        solver.set_config(config.with_survival_search(survival_search));
+        solver.think()
+    };
+    //a 7 next to the 3 makes 2 chains
+    let mut fire_packs = packs.clone();
+    fire_packs[0] = Pack::new(&[7, 0, 0, 0]);
+    //close to the danger line but nothing falls, the fire of the beam search plan stands
+    let player = GameStatus::default().with_board(board).with_rest_time_milliseconds(180000);
+    assert!(survival::in_danger(&board, 0));
+    let result = think(player.clone(), &fire_packs, true);
+    assert_eq!((result.search_depth, result.last_chain_count), (0, 2));
+    assert_eq!(result.principal_variation, think(player.clone(), &fire_packs, false).principal_variation);
+
+    //the second line kills every beam search plan
+    let player = player.with_obstacle_block_count(20);
+    let plan = SurvivalSearch::default()
+        .search(
+            &mut Simulator::new(),
+            &vec![Pack::new(&[2, 2, 0, 0]).unique_rotations(); MAX_TURN],
+            0,
+            &player,
+            &GameStatus::default(),
+            &AttackSchedule::new(),
+        )
+        .unwrap();
+    assert_eq!(think(player.clone(), &packs, true).principal_variation, plan.principal_variation);
+
+    //after the first line the 2 chains offset 1 obstacle and leave less than a line, the fire
+    //survives without killing
+    let result = think(player.clone(), &fire_packs, true);
+    assert_eq!((result.search_depth, result.last_chain_count), (0, 2));
+    assert_eq!(result.pending_obstacle_block_count, 9);
+    assert_eq!(result.principal_variation, think(player, &fire_packs, false).principal_variation);
+}
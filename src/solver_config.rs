@@ -1,3 +1,18 @@
+use crate::lethal::DEFAULT_LETHAL_NODE_LIMIT;
+
+//how think walks the depths of the search
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SearchStrategy {
+    //every state of a depth is expanded before the next depth, keeping beam_width per depth
+    Beam,
+    //passes expand the best width states of every depth until the time or node budget runs out
+    Chokudai {
+        width: usize,
+        time_limit: std::time::Duration,
+        node_limit: usize,
+    },
+}
+
 #[derive(Debug)]
 pub struct SolverConfig {
     beam_depth: usize,
@@ -5,7 +20,20 @@ pub struct SolverConfig {
     gaze_beam_depth: usize,
     gaze_beam_width: usize,
     pub fire_max_chain_count: u8,
-
+    evaluate_cache_bytes: usize,
+    search_strategy: SearchStrategy,
+    //0 keeps the beam search check before an immediate fire
+    fire_timing_iterations: usize,
+    //inject the enemy attack predicted by a beam of gaze_beam on the enemy board
+    enemy_attack_prediction: bool,
+    //answer pending obstacles with the counter search before the beam search
+    counter_search: bool,
+    //close to the danger line, play the survival search when the beam search plan doesn't survive
+    survival_search: bool,
+    //take an immediate fire which the lethal check proves kills, before fire timing or the gaze check
+    lethal_check: bool,
+    //nodes of the lethal check before an immediate fire with plenty of time left
+    lethal_node_limit: usize,
 }
 pub const DEFAULT_BEAM_DEPTH: usize = 12;
 pub const DEFAULT_BEAM_WIDTH: usize = 1500;
@@ -14,6 +42,10 @@ pub const DEFAULT_FATAL_FIRE_MAX_CHAIN_COUNT: u8 = 15;
 //parameters for gazing enemy
 pub const DEFAULT_GAZE_BEAM_DEPTH: usize = 3;
 pub const DEFAULT_GAZE_BEAM_WIDTH: usize = 50;
+//states of every depth Chokudai search expands in a pass
+pub const DEFAULT_CHOKUDAI_WIDTH: usize = 1;
+//memory for memoizing evaluations
+pub const DEFAULT_EVALUATE_CACHE_BYTES: usize = 256 * 1024 * 1024;
 
 
 pub const SOLVER_VERSION: &str = "4.0";
@@ -23,8 +55,16 @@ impl SolverConfig {
             beam_depth: DEFAULT_BEAM_DEPTH,
             beam_width: DEFAULT_BEAM_WIDTH,
             gaze_beam_depth: DEFAULT_GAZE_BEAM_DEPTH,
-            gaze_beam_width: DEFAULT_BEAM_WIDTH,
+            gaze_beam_width: DEFAULT_GAZE_BEAM_WIDTH,
             fire_max_chain_count: DEFAULT_FIRE_MAX_CHAIN_COUNT,
+            evaluate_cache_bytes: DEFAULT_EVALUATE_CACHE_BYTES,
+            search_strategy: SearchStrategy::Beam,
+            fire_timing_iterations: 0,
+            enemy_attack_prediction: false,
+            counter_search: false,
+            survival_search: false,
+            lethal_check: false,
+            lethal_node_limit: DEFAULT_LETHAL_NODE_LIMIT,
         }
     }
     pub fn new(
@@ -40,6 +80,14 @@ impl SolverConfig {
             gaze_beam_depth,
             gaze_beam_width,
             fire_max_chain_count,
+            evaluate_cache_bytes: DEFAULT_EVALUATE_CACHE_BYTES,
+            search_strategy: SearchStrategy::Beam,
+            fire_timing_iterations: 0,
+            enemy_attack_prediction: false,
+            counter_search: false,
+            survival_search: false,
+            lethal_check: false,
+            lethal_node_limit: DEFAULT_LETHAL_NODE_LIMIT,
         }
     }
     pub fn with_beam(mut self, beam_depth: usize, beam_width: usize) -> SolverConfig {
@@ -47,6 +95,67 @@ impl SolverConfig {
         self.beam_width = beam_width;
         self
     }
+    pub fn with_evaluate_cache_bytes(mut self, evaluate_cache_bytes: usize) -> SolverConfig {
+        self.evaluate_cache_bytes = evaluate_cache_bytes;
+        self
+    }
+    pub fn evaluate_cache_bytes(&self) -> usize {
+        self.evaluate_cache_bytes
+    }
+    pub fn with_search_strategy(mut self, search_strategy: SearchStrategy) -> SolverConfig {
+        self.search_strategy = search_strategy;
+        self
+    }
+    pub fn search_strategy(&self) -> SearchStrategy {
+        self.search_strategy
+    }
+    pub fn with_fire_timing_iterations(mut self, fire_timing_iterations: usize) -> SolverConfig {
+        self.fire_timing_iterations = fire_timing_iterations;
+        self
+    }
+    pub fn fire_timing_iterations(&self) -> usize {
+        self.fire_timing_iterations
+    }
+    pub fn with_gaze_beam(mut self, gaze_beam_depth: usize, gaze_beam_width: usize) -> SolverConfig {
+        self.gaze_beam_depth = gaze_beam_depth;
+        self.gaze_beam_width = gaze_beam_width;
+        self
+    }
+    pub fn with_enemy_attack_prediction(mut self, enemy_attack_prediction: bool) -> SolverConfig {
+        self.enemy_attack_prediction = enemy_attack_prediction;
+        self
+    }
+    pub fn enemy_attack_prediction(&self) -> bool {
+        self.enemy_attack_prediction
+    }
+    pub fn with_counter_search(mut self, counter_search: bool) -> SolverConfig {
+        self.counter_search = counter_search;
+        self
+    }
+    pub fn counter_search(&self) -> bool {
+        self.counter_search
+    }
+    pub fn with_survival_search(mut self, survival_search: bool) -> SolverConfig {
+        self.survival_search = survival_search;
+        self
+    }
+    pub fn survival_search(&self) -> bool {
+        self.survival_search
+    }
+    pub fn with_lethal_check(mut self, lethal_check: bool) -> SolverConfig {
+        self.lethal_check = lethal_check;
+        self
+    }
+    pub fn lethal_check(&self) -> bool {
+        self.lethal_check
+    }
+    pub fn with_lethal_node_limit(mut self, lethal_node_limit: usize) -> SolverConfig {
+        self.lethal_node_limit = lethal_node_limit;
+        self
+    }
+    pub fn lethal_node_limit(&self) -> usize {
+        self.lethal_node_limit
+    }
     pub fn beam(&self) -> (usize, usize) {
         (self.beam_depth, self.beam_width)
     }
@@ -0,0 +1,222 @@
+//A search for staying alive when the board is close to the danger line. Pending obstacles fall a
+//line at the start of every turn, plans are ranked by the turns they survive counting the lines
+//still to fall after them, then by their depth, then by the room left once every pending line has
+//fallen, then by the obstacles their chains clear.
+use crate::attack_schedule::AttackSchedule;
+use crate::beam::Beam;
+use crate::board::{Board, DANGER_LINE_HEIGHT, FIELD_WIDTH};
+use crate::command::Command;
+use crate::game_status::GameStatus;
+use crate::pack::Pack;
+use crate::search_state::SearchState;
+use crate::simulator::Simulator;
+use crate::solver::{principal_variation, Transition};
+use crate::transposition::TranspositionSet;
+
+pub const DEFAULT_SURVIVAL_BEAM_DEPTH: usize = 6;
+pub const DEFAULT_SURVIVAL_BEAM_WIDTH: usize = 200;
+//a pack is two blocks high
+const PACK_HEIGHT: usize = 2;
+
+//the pending lines and the next pack could reach the danger line
+pub fn in_danger(board: &Board, obstacle_block_count: u32) -> bool {
+    let max_height = board.heights.iter().max().cloned().unwrap_or(0);
+    let lines = (obstacle_block_count / FIELD_WIDTH as u32) as usize;
+    max_height + lines + PACK_HEIGHT >= DANGER_LINE_HEIGHT
+}
+
+//obstacles left to fall after a state at the depth, with the attacks predicted to arrive later
+pub fn pending_obstacle_block_count(state: &SearchState, depth: usize, enemy_attacks: &AttackSchedule) -> u32 {
+    let later_attack: u32 = enemy_attacks.iter().filter(|&(arrival_depth, _)| arrival_depth > depth).map(|(_, count)| count).sum();
+    (state.obstacle_block_count() + later_attack).saturating_sub(state.spawn_obstacle_block_count())
+}
+
+//the pending lines which fall a line a turn before one kills, and the board after them
+pub fn fall_pending_lines(board: &Board, obstacle_block_count: u32) -> (usize, Board) {
+    let mut board = *board;
+    let lines = (obstacle_block_count / FIELD_WIDTH as u32) as usize;
+    for fallen in 0..lines {
+        board.drop_obstacles();
+        if board.is_game_over() {
+            return (fallen, board);
+        }
+    }
+    (lines, board)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SurvivalPlan {
+    //commands from this turn
+    pub principal_variation: Vec<Command>,
+    //turns of the plan and the pending lines which fall after it without killing
+    pub survived_turns: usize,
+    //obstacles offset or sent by the chains of the plan
+    pub cleared_obstacle_block_count: u32,
+    //rows left under the danger line once every pending line has fallen, 0 if they kill
+    pub room: usize,
+    pub board: Board,
+}
+
+impl SurvivalPlan {
+    pub fn command(&self) -> Command {
+        self.principal_variation[0]
+    }
+    pub fn turns(&self) -> usize {
+        self.principal_variation.len()
+    }
+}
+
+pub struct SurvivalSearch {
+    beam_depth: usize,
+    beam_width: usize,
+}
+
+impl SurvivalSearch {
+    pub fn default() -> SurvivalSearch {
+        SurvivalSearch { beam_depth: DEFAULT_SURVIVAL_BEAM_DEPTH, beam_width: DEFAULT_SURVIVAL_BEAM_WIDTH }
+    }
+    pub fn with_beam(mut self, beam_depth: usize, beam_width: usize) -> Self {
+        self.beam_depth = beam_depth;
+        self.beam_width = beam_width;
+        self
+    }
+    //the plan which survives the most turns, None if every drop dies this turn
+    pub fn search(
+        &self,
+        simulator: &mut Simulator,
+        packs: &[Vec<(Pack, usize)>],
+        turn: usize,
+        player: &GameStatus,
+        enemy: &GameStatus,
+        enemy_attacks: &AttackSchedule,
+    ) -> Option<SurvivalPlan> {
+        let mut beams: Vec<Beam> = (0..self.beam_depth + 1).map(|_| Beam::with_capacity(self.beam_width + 1)).collect();
+        let root = SearchState::default()
+            .with_board(player.board())
+            .with_obstacle_block_count(player.obstacle_block_count())
+            .with_spawn_obstacle_block_count(enemy.obstacle_block_count())
+            .with_cumulative_game_score(player.cumulative_game_score());
+        beams[0].push(root);
+        let mut searched_state = TranspositionSet::new();
+        let mut transitions = vec![Transition::root()];
+        //obstacles offset or sent by the plan up to each transition
+        let mut cleared_obstacle_block_counts = vec![0];
+        //the survived turns, the depth, the score, the history, the room and the board of the best state
+        let mut best: Option<(usize, usize, f64, u32, usize, Board)> = None;
+        let mut children = Vec::with_capacity(4 * 9);
+        for depth in 0..self.beam_depth {
+            let search_turn = turn + depth;
+            if search_turn >= packs.len() {
+                break;
+            }
+            while let Some(mut search_state) = beams[depth].pop_max() {
+                let attack = enemy_attacks.obstacle_block_count(depth);
+                search_state.set_obstacle_block_count(search_state.obstacle_block_count() + attack);
+                search_state.update_obstacle_block_and_drop();
+                search_state.board().debug_check_invariants();
+                children.clear();
+                children.extend(simulator.expand(&search_state.board(), &packs[search_turn]));
+                for child in children.iter() {
+                    if child.board.is_game_over() {
+                        continue;
+                    }
+                    let mut next_search_state = search_state.after_drop(child);
+                    if !searched_state.insert(&next_search_state) {
+                        continue;
+                    }
+                    transitions.push(Transition::from_child(search_state.history(), child));
+                    next_search_state.set_history((transitions.len() - 1) as u32);
+                    //the chain offsets pending obstacles first and sends the rest
+                    let offset = search_state.obstacle_block_count() - next_search_state.obstacle_block_count();
+                    let sent = next_search_state.spawn_obstacle_block_count() - search_state.spawn_obstacle_block_count();
+                    let cleared = cleared_obstacle_block_counts[search_state.history() as usize] + offset + sent;
+                    cleared_obstacle_block_counts.push(cleared);
+
+                    let pending = pending_obstacle_block_count(&next_search_state, depth, enemy_attacks);
+                    let (fallen_lines, board) = fall_pending_lines(&child.board, pending);
+                    let survived_turns = depth + 1 + fallen_lines;
+                    let room = if fallen_lines == (pending / FIELD_WIDTH as u32) as usize {
+                        DANGER_LINE_HEIGHT - board.heights.iter().max().cloned().unwrap_or(0)
+                    } else {
+                        0
+                    };
+                    //flat boards take the lines best, the room of every column counts a little
+                    let total_room: usize = board.heights.iter().map(|&height| DANGER_LINE_HEIGHT.saturating_sub(height)).sum();
+                    let score = room as f64 * 1e4 + cleared as f64 * 100.0 + total_room as f64;
+                    next_search_state.set_search_score(score);
+                    let key = (survived_turns, depth, score);
+                    if best.is_none_or(|(best_turns, best_depth, best_score, ..)| key > (best_turns, best_depth, best_score)) {
+                        best = Some((survived_turns, depth, score, next_search_state.history(), room, child.board));
+                    }
+                    beams[depth + 1].push_bounded(next_search_state, self.beam_width);
+                }
+            }
+        }
+        let (survived_turns, _, _, history, room, board) = best?;
+        Some(SurvivalPlan {
+            principal_variation: principal_variation(&transitions, history),
+            survived_turns,
+            cleared_obstacle_block_count: cleared_obstacle_block_counts[history as usize],
+            room,
+            board,
+        })
+    }
+}
+
+#[test]
+fn test_survival_search() {
+    assert!(!in_danger(&Board::default(), 50));
+    //two lines from the top, the second line kills unless a chain offsets it
+    let board = crate::test_fixtures::two_lines_from_top();
+    assert!(in_danger(&board, 0));
+    let player = GameStatus::default().with_board(board).with_obstacle_block_count(20);
+    let enemy = GameStatus::default();
+    let mut simulator = Simulator::new();
+    let search = SurvivalSearch::default().with_beam(4, 100);
+    let ones = vec![Pack::new(&[1, 1, 0, 0]).unique_rotations(); 10];
+    //the first line falls this turn, the second one on the next
+    let plan = search.search(&mut simulator, &ones, 0, &player, &enemy, &AttackSchedule::new()).unwrap();
+    assert_eq!((plan.turns(), plan.survived_turns, plan.room), (1, 1, 0));
+
+    //a 7 next to the 3 after the first line makes 2 chains which offset 1 obstacle
+    let mut packs = ones.clone();
+    packs[0] = Pack::new(&[7, 0, 0, 0]).unique_rotations();
+    let plan = search.search(&mut simulator, &packs, 0, &player, &enemy, &AttackSchedule::new()).unwrap();
+    assert_eq!(plan.turns(), 4);
+    assert_eq!(plan.survived_turns, 4);
+    assert_eq!(plan.cleared_obstacle_block_count, crate::simulator::calculate_obstacle_count_from_chain_count(2));
+    assert!(plan.room > 0);
+    //an attack fired on the second turn falls on the third
+    let mut attacks = AttackSchedule::new();
+    attacks.add_fire(1, 30);
+    let plan = search.search(&mut simulator, &packs, 0, &player, &enemy, &attacks).unwrap();
+    assert_eq!(plan.turns(), 2);    //the attack is counted when it falls after the plan
+    assert_eq!(plan.survived_turns, 2);
+
+    //only the last column has room for the next packs and five lines are coming, every plan which
+    //drops a second pack dies under the lines still to fall
+    let board: Board = "
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        #########.
+        #########.
+        #########.
+        ##########
+        ##########
+        ##########
+        ##########
+        ##########
+        ##########
+        ##########
+        ##########
+    "
+    .parse()
+    .unwrap();
+    let player = GameStatus::default().with_board(board).with_obstacle_block_count(50);
+    let plan = search.search(&mut simulator, &ones, 0, &player, &enemy, &AttackSchedule::new()).unwrap();
+    assert_eq!((plan.turns(), plan.survived_turns, plan.room), (1, 5, 1));
+    assert_eq!(plan.command(), Command::Drop((8, 1)));
+}
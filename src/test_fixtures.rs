@@ -0,0 +1,93 @@
+//Boards and packs shared by the tests.
+use crate::board::Board;
+use crate::pack::Pack;
+use crate::solver::MAX_TURN;
+use crate::xorshift::Xorshift;
+
+//a 7 next to the 3 erases it, then the 9 falls next to the 1 for 2 chains
+pub fn seven_next_to_three() -> Board {
+    "
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        .....9....
+        .....3....
+        ....12....
+        #...415...
+    "
+    .parse()
+    .unwrap()
+}
+
+//9 of 10 columns a line from the top
+pub fn wall() -> Board {
+    "#########.\n".repeat(16).parse().unwrap()
+}
+
+//two lines from the top, a 7 next to the 3 makes 2 chains
+pub fn two_lines_from_top() -> Board {
+    "
+        ..........
+        ######....
+        ######....
+        ######....
+        ######....
+        ######....
+        ######....
+        ######....
+        ######....
+        ######....
+        ######....
+        ######....
+        ######.9..
+        ######.3..
+        ######12..
+        ######415.
+    "
+    .parse()
+    .unwrap()
+}
+
+//the pack [0, 7, 5, 6] fires 9 chains, also after a line of obstacles
+pub fn nine_chains() -> Board {
+    "
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ...1......
+        ..43.4....
+        ..3453....
+        ..8839....
+        ..6683....
+        .2751512..
+        .77621324.
+    "
+    .parse()
+    .unwrap()
+}
+
+//packs of a whole game
+pub fn random_packs(seed: u64) -> Vec<Pack> {
+    let mut rnd = Xorshift::with_seed(seed);
+    (0..MAX_TURN)
+        .map(|_| {
+            let blocks = [0; 4].map(|_| rnd.rand(10) as u8);
+            Pack::new(&blocks)
+        })
+        .collect()
+}
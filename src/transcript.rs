@@ -0,0 +1,130 @@
+use crate::match_record::{COMMAND_PREFIX, COMMENT_PREFIX};
+use std::cell::RefCell;
+use std::io::{BufRead, Read, Write};
+use std::rc::Rc;
+use std::time::Instant;
+
+//Writes a live game in the match record format. Every byte read from stdin is copied as is,
+//commands are written as "> command" and notes as "# key value".
+pub struct Transcript<W: Write> {
+    writer: W,
+    start: Instant,
+}
+
+impl<W: Write> Transcript<W> {
+    pub fn new(writer: W) -> Transcript<W> {
+        Transcript { writer, start: Instant::now() }
+    }
+    pub fn input(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.writer.write_all(bytes)
+    }
+    pub fn command(&mut self, command: &str) -> std::io::Result<()> {
+        writeln!(self.writer, "{} {}", COMMAND_PREFIX, command)?;
+        self.writer.flush()
+    }
+    pub fn note(&mut self, key: &str, value: &str) -> std::io::Result<()> {
+        writeln!(self.writer, "{} {} {}", COMMENT_PREFIX, key, value)
+    }
+    //milliseconds since the transcript started
+    pub fn elapsed_milliseconds(&self) -> u128 {
+        self.start.elapsed().as_millis()
+    }
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+//copies the bytes the protocol reader consumes into a transcript, a transcript that can't be
+//written must not stop the game so write errors are ignored
+pub struct TeeReader<R, W: Write> {
+    reader: R,
+    transcript: Rc<RefCell<Transcript<W>>>,
+}
+
+impl<R: BufRead, W: Write> TeeReader<R, W> {
+    pub fn new(reader: R, transcript: Rc<RefCell<Transcript<W>>>) -> TeeReader<R, W> {
+        TeeReader { reader, transcript }
+    }
+}
+
+impl<R: BufRead, W: Write> Read for TeeReader<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let size = self.reader.read(buf)?;
+        let _ = self.transcript.borrow_mut().input(&buf[..size]);
+        Ok(size)
+    }
+}
+
+impl<R: BufRead, W: Write> BufRead for TeeReader<R, W> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.reader.fill_buf()
+    }
+    fn consume(&mut self, amt: usize) {
+        //the data is still buffered so this doesn't read again
+        if let Ok(buf) = self.reader.fill_buf() {
+            let amt = std::cmp::min(amt, buf.len());
+            let _ = self.transcript.borrow_mut().input(&buf[..amt]);
+        }
+        self.reader.consume(amt);
+    }
+}
+
+#[test]
+fn test_transcript() {
+    use crate::command::Command;
+    use crate::match_record::MatchRecord;
+    use crate::protocol::ProtocolReader;
+
+    //the protocol input without our lines
+    let input: String = crate::match_record::sample_record_text()
+        .lines()
+        .filter(|line| !line.starts_with(COMMAND_PREFIX) && !line.starts_with(COMMENT_PREFIX))
+        .map(|line| format!("{}\n", line))
+        .collect();
+    let transcript = Rc::new(RefCell::new(Transcript::new(Vec::new())));
+    transcript.borrow_mut().command("togatog_ai_4.0").unwrap();
+    transcript.borrow_mut().note("seed", "1024").unwrap();
+    let mut reader = ProtocolReader::new(TeeReader::new(input.as_bytes(), transcript.clone()));
+    reader.read_raw_packs().unwrap();
+    while let Some(turn) = reader.read_turn().unwrap() {
+        reader.read_game_status().unwrap();
+        reader.read_game_status().unwrap();
+        let mut transcript = transcript.borrow_mut();
+        transcript.command(&Command::Drop((turn, 1)).to_string()).unwrap();
+        transcript.note("think_ms", "5").unwrap();
+    }
+    drop(reader);
+    let text = String::from_utf8(Rc::try_unwrap(transcript).ok().unwrap().into_inner().into_inner()).unwrap();
+    //every input byte is kept
+    let kept: String = text
+        .lines()
+        .filter(|line| !line.starts_with(COMMAND_PREFIX) && !line.starts_with(COMMENT_PREFIX))
+        .map(|line| format!("{}\n", line))
+        .collect();
+    assert_eq!(kept, input);
+
+    let record = MatchRecord::parse(&text).unwrap();
+    assert_eq!(record.note("seed"), Some("1024"));
+    assert_eq!(record.turns.len(), 2);
+    assert_eq!(record.turns[0].command, Some(Command::Drop((0, 1))));
+    assert_eq!(record.turns[1].command, Some(Command::Drop((1, 1))));
+    assert_eq!(record.turns[1].note("think_ms"), Some("5"));
+
+    //a broken transcript doesn't stop reading
+    struct Broken;
+    impl Write for Broken {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("broken"))
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let transcript = Rc::new(RefCell::new(Transcript::new(Broken)));
+    let mut reader = TeeReader::new(input.as_bytes(), transcript.clone());
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest).unwrap();
+    assert_eq!(line.len() + rest.len(), input.len());
+}
@@ -0,0 +1,165 @@
+//Keys for deduplicating beam search states. A key covers everything which decides the future
+//of a state: the board, the score, both obstacle counters and the skill points.
+use crate::board::CompactBoard;
+use crate::search_state::SearchState;
+use fnv::{FnvHashMap, FnvHashSet};
+
+#[cfg(not(feature = "wide-transposition-key"))]
+pub type TranspositionKey = u64;
+//the board zobrist hash and a second independent hash of the board
+#[cfg(feature = "wide-transposition-key")]
+pub type TranspositionKey = u128;
+
+//splitmix64 finalizer
+#[inline]
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[inline]
+fn counters_hash(state: &SearchState) -> u64 {
+    let scores = (state.cumulative_game_score() as u64) << 32 | state.obstacle_block_count() as u64;
+    let rest = (state.spawn_obstacle_block_count() as u64) << 8 | state.skill_point() as u64;
+    mix(mix(scores) ^ rest)
+}
+
+#[cfg(not(feature = "wide-transposition-key"))]
+#[inline]
+pub fn transposition_key(state: &SearchState) -> TranspositionKey {
+    state.compact_board().zobrist_hash() ^ counters_hash(state)
+}
+
+#[cfg(feature = "wide-transposition-key")]
+#[inline]
+pub fn transposition_key(state: &SearchState) -> TranspositionKey {
+    use std::hash::{Hash, Hasher};
+    let counters = counters_hash(state);
+    let mut hasher = fnv::FnvHasher::default();
+    state.compact_board().hash(&mut hasher);
+    let high = state.compact_board().zobrist_hash() ^ counters;
+    let low = hasher.finish() ^ mix(counters);
+    (high as u128) << 64 | low as u128
+}
+
+//the part of a state the key stands for
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct StateIdentity {
+    board: CompactBoard,
+    cumulative_game_score: u32,
+    obstacle_block_count: u32,
+    spawn_obstacle_block_count: u32,
+    skill_point: u32,
+}
+
+impl StateIdentity {
+    fn new(state: &SearchState) -> StateIdentity {
+        StateIdentity {
+            board: state.compact_board(),
+            cumulative_game_score: state.cumulative_game_score(),
+            obstacle_block_count: state.obstacle_block_count(),
+            spawn_obstacle_block_count: state.spawn_obstacle_block_count(),
+            skill_point: state.skill_point(),
+        }
+    }
+}
+
+//states seen in one search
+pub struct TranspositionSet {
+    keys: FnvHashSet<TranspositionKey>,
+    //Some when collisions are counted, the first state of every key
+    identities: Option<FnvHashMap<TranspositionKey, StateIdentity>>,
+    collision_count: u64,
+}
+
+impl Default for TranspositionSet {
+    fn default() -> TranspositionSet {
+        TranspositionSet::new()
+    }
+}
+
+impl TranspositionSet {
+    pub fn new() -> TranspositionSet {
+        TranspositionSet { keys: FnvHashSet::default(), identities: None, collision_count: 0 }
+    }
+    //keeps every state to tell a collision from a transposition, for debugging
+    pub fn with_collision_count() -> TranspositionSet {
+        TranspositionSet { keys: FnvHashSet::default(), identities: Some(FnvHashMap::default()), collision_count: 0 }
+    }
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+    //different states which got the same key
+    pub fn collision_count(&self) -> u64 {
+        self.collision_count
+    }
+    //true if no state with the key was inserted before
+    pub fn insert(&mut self, state: &SearchState) -> bool {
+        let key = transposition_key(state);
+        let inserted = self.keys.insert(key);
+        if let Some(identities) = self.identities.as_mut() {
+            let identity = StateIdentity::new(state);
+            if *identities.entry(key).or_insert(identity) != identity {
+                self.collision_count += 1;
+            }
+        }
+        inserted
+    }
+}
+
+#[test]
+fn test_transposition_key() {
+    let board: crate::board::Board = "
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ....5.....
+        ...#41....
+        ..2389....
+    "
+    .parse()
+    .unwrap();
+    let state = SearchState::default().with_board(board).with_cumulative_game_score(30);
+    let key = transposition_key(&state);
+    //every counter is part of the key, the score isn't clamped
+    let others = [
+        state.with_obstacle_block_count(10),
+        state.with_spawn_obstacle_block_count(10),
+        state.with_skill_point(5),
+        state.with_cumulative_game_score(31),
+        state.with_board(crate::board::Board::default()),
+        state.with_cumulative_game_score(5000),
+    ];
+    for other in others.iter() {
+        assert_ne!(transposition_key(other), key);
+    }
+    assert_ne!(transposition_key(&others[5]), transposition_key(&state.with_cumulative_game_score(6000)));
+    //the transition doesn't matter
+    assert_eq!(transposition_key(&state.with_search_score(3.0).with_chain_count(2)), key);
+
+    let mut set = TranspositionSet::with_collision_count();
+    assert!(set.insert(&state));
+    assert!(!set.insert(&state.with_search_score(1.0)));
+    assert!(set.insert(&others[0]));
+    assert_eq!((set.len(), set.collision_count()), (2, 0));
+    //a forged collision, the key of another state
+    set.keys.clear();
+    set.identities.as_mut().unwrap().insert(transposition_key(&others[1]), StateIdentity::new(&state));
+    assert!(set.insert(&others[1]));
+    assert_eq!(set.collision_count(), 1);
+}
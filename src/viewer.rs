@@ -0,0 +1,252 @@
+use crate::board::{block_char, Board, FIELD_HEIGHT, FIELD_WIDTH};
+use crate::command::Command;
+use crate::game_status::GameStatus;
+use crate::match_record::MatchRecord;
+use crate::pack::Pack;
+use crate::simulator::Simulator;
+
+pub const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+//one player's board at a stage of a turn
+#[derive(Debug, Clone)]
+pub struct Side {
+    pub label: String,
+    pub board: Board,
+    pub chain_count: u8,
+    pub command: Option<Command>,
+    pub cumulative_game_score: u32,
+    pub skill_point: u32,
+    pub obstacle_block_count: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub turn: usize,
+    pub player: Side,
+    pub enemy: Option<Side>,
+}
+
+//turn start, obstacles, the dropped pack and every chain
+pub fn turn_stages(
+    simulator: &mut Simulator,
+    game_status: &GameStatus,
+    pack: &Pack,
+    command: Option<Command>,
+) -> Vec<Side> {
+    let side = Side {
+        label: "turn start".to_string(),
+        board: game_status.board(),
+        chain_count: 0,
+        command,
+        cumulative_game_score: game_status.cumulative_game_score(),
+        skill_point: game_status.skill_point(),
+        obstacle_block_count: game_status.obstacle_block_count(),
+    };
+    let mut board = game_status.board();
+    let mut stages = vec![side.clone()];
+    if game_status.obstacle_block_count() >= FIELD_WIDTH as u32 {
+        board.drop_obstacles();
+        stages.push(Side {
+            label: "obstacles".to_string(),
+            board,
+            obstacle_block_count: side.obstacle_block_count - FIELD_WIDTH as u32,
+            ..side.clone()
+        });
+    }
+    let obstacle_block_count = stages.last().unwrap().obstacle_block_count;
+    if let Some(Command::Drop((point, rotate_count))) = command {
+        let pack = pack.rotated(rotate_count);
+        if (point..point + 2).all(|x| board.heights[x] + 2 <= FIELD_HEIGHT) {
+            let boards = simulator.simulate_with_stages(&mut board, point, &pack);
+            for (chain_count, board) in boards.into_iter().enumerate() {
+                let label = if chain_count == 0 {
+                    format!("drop {}", command.unwrap())
+                } else {
+                    format!("chain {}", chain_count)
+                };
+                stages.push(Side {
+                    label,
+                    board,
+                    chain_count: chain_count as u8,
+                    obstacle_block_count,
+                    ..side.clone()
+                });
+            }
+        }
+    } else if let Some(Command::Spell) = command {
+        let (_, mut boards) = simulator.spell_with_stages(&mut board);
+        if boards.is_empty() {
+            //nothing to explode
+            boards.push(board);
+        }
+        //the first stage is the explosion itself
+        for (chain_count, board) in boards.into_iter().enumerate() {
+            let label = if chain_count == 0 {
+                "spell".to_string()
+            } else {
+                format!("chain {}", chain_count)
+            };
+            stages.push(Side {
+                label,
+                board,
+                chain_count: chain_count as u8,
+                obstacle_block_count,
+                ..side.clone()
+            });
+        }
+    }
+    stages
+}
+
+pub fn record_frames(record: &MatchRecord) -> Vec<Frame> {
+    let mut simulator = Simulator::new();
+    let mut frames = Vec::new();
+    for (i, turn_record) in record.turns.iter().enumerate() {
+        let pack = record.pack(turn_record.turn);
+        let player = turn_stages(&mut simulator, &turn_record.player, &pack, turn_record.command);
+        let enemy_command = record.infer_enemy_command(i, &mut simulator);
+        let enemy = turn_stages(&mut simulator, &turn_record.enemy, &pack, enemy_command);
+        for stage in 0..std::cmp::max(player.len(), enemy.len()) {
+            frames.push(Frame {
+                turn: turn_record.turn,
+                player: player[std::cmp::min(stage, player.len() - 1)].clone(),
+                enemy: Some(enemy[std::cmp::min(stage, enemy.len() - 1)].clone()),
+            });
+        }
+    }
+    frames
+}
+
+pub fn board_frames(game_status: &GameStatus, pack: &Pack, command: Command) -> Vec<Frame> {
+    turn_stages(&mut Simulator::new(), game_status, pack, Some(command))
+        .into_iter()
+        .map(|player| Frame { turn: 0, player, enemy: None })
+        .collect()
+}
+
+fn side_lines(side: &Side, name: &str, color: bool) -> Vec<String> {
+    let display = side.board.display().with_hidden_rows(true).with_color(color);
+    let mut lines = vec![format!("{:<12}", name), format!("{:<12}", side.label)];
+    for row in 0..display.rows() {
+        //hidden rows are above the line
+        let edge = if row < FIELD_HEIGHT - crate::board::INPUT_FIELD_HEIGHT { ' ' } else { '|' };
+        lines.push(format!("{}{}{}", edge, display.row(row), edge));
+    }
+    lines.push(format!("{:<12}", format!("score {}", side.cumulative_game_score)));
+    lines.push(format!("{:<12}", format!("skill {}", side.skill_point)));
+    lines.push(format!("{:<12}", format!("obstacle {}", side.obstacle_block_count)));
+    let command = side.command.map(|c| c.to_string()).unwrap_or_else(|| "?".to_string());
+    lines.push(format!("{:<12}", format!("command {}", command)));
+    lines
+}
+
+//upcoming packs side by side as they are before rotation
+pub fn render_packs(packs: &[Pack]) -> String {
+    let mut rows = [String::new(), String::new()];
+    for pack in packs.iter() {
+        for (y, row) in rows.iter_mut().enumerate() {
+            row.push(block_char(pack.get(2 * y)));
+            row.push(block_char(pack.get(2 * y + 1)));
+            row.push_str("  ");
+        }
+    }
+    format!("{}\n{}\n", rows[0].trim_end(), rows[1].trim_end())
+}
+
+pub fn render_frame(frame: &Frame, color: bool) -> String {
+    let player = side_lines(&frame.player, "player", color);
+    let mut text = format!("turn {}\n", frame.turn);
+    match &frame.enemy {
+        Some(enemy) => {
+            let enemy = side_lines(enemy, "enemy", color);
+            for (left, right) in player.iter().zip(enemy.iter()) {
+                text.push_str(&format!("{}    {}\n", left, right));
+            }
+        }
+        None => {
+            for line in player.iter() {
+                text.push_str(&format!("{}\n", line));
+            }
+        }
+    }
+    text
+}
+
+#[test]
+fn test_record_frames() {
+    let record = MatchRecord::parse(&crate::match_record::sample_record_text()).unwrap();
+    let frames = record_frames(&record);
+    //turn start and drop for both turns
+    assert_eq!(frames.len(), 4);
+    assert_eq!(frames[1].player.label, "drop 8 0");
+    assert_eq!(frames[1].player.board, record.turns[1].player.board());
+    assert_eq!(frames[1].enemy.as_ref().unwrap().command, Some(Command::Drop((8, 0))));
+    assert_eq!(frames[3].enemy.as_ref().unwrap().label, "turn start");
+
+    let text = render_frame(&frames[1], false);
+    assert!(text.starts_with("turn 0\n"));
+    assert!(text.contains("|........94|    |........94|"));
+}
+
+#[test]
+fn test_board_frames() {
+    let board: Board = "
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        .....3....
+        .....2....
+        .....1....
+    "
+    .parse()
+    .unwrap();
+    let game_status = GameStatus::default().with_board(board).with_obstacle_block_count(12);
+    let frames = board_frames(&game_status, &Pack::new(&[7, 0, 8, 0]), Command::Drop((4, 1)));
+    let labels: Vec<&str> = frames.iter().map(|f| f.player.label.as_str()).collect();
+    assert_eq!(labels, vec!["turn start", "obstacles", "drop 4 1", "chain 1"]);
+    assert_eq!(frames[3].player.chain_count, 1);
+    assert_eq!(frames[3].player.obstacle_block_count, 2);
+    assert_eq!(
+        render_packs(&[Pack::new(&[7, 0, 8, 0]), Pack::new(&[0, 1, 2, 3])]),
+        "7.  .1\n8.  23\n"
+    );
+}
+
+#[test]
+fn test_spell_frames() {
+    let board: Board = "
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ..........
+        ....2.....
+        ....5.....
+        ....3.8...
+    "
+    .parse()
+    .unwrap();
+    let game_status = GameStatus::default().with_board(board);
+    let frames = board_frames(&game_status, &Pack::new(&[7, 0, 8, 0]), Command::Spell);
+    let labels: Vec<&str> = frames.iter().map(|f| f.player.label.as_str()).collect();
+    assert_eq!(labels, vec!["turn start", "spell"]);
+    assert_eq!(frames[1].player.board.heights, [0, 0, 0, 0, 0, 0, 1, 0, 0, 0]);
+}